@@ -0,0 +1,98 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Everything that can stop the game from starting or drawing.
+#[derive(Debug)]
+pub enum Error {
+    EventLoop(winit::error::EventLoopError),
+    Window(winit::error::OsError),
+    CreateSurface(wgpu::CreateSurfaceError),
+    /// None of the selected backends has a usable adapter.
+    NoAdapter,
+    RequestDevice(wgpu::RequestDeviceError),
+    /// The surface ran out of memory, nothing more can be drawn.
+    OutOfMemory,
+    Image(image::ImageError),
+    /// A shader failed to compile or doesn't match its pipeline.
+    Shader(wgpu::Error),
+    /// A buffer could not be read back from the graphics device.
+    BufferMap(wgpu::BufferAsyncError),
+    Io { path: PathBuf, source: io::Error },
+    Model { path: PathBuf, source: obj::ObjError },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::EventLoop(e) => write!(f, "could not start the event loop: {}", e),
+            Error::Window(e) => write!(f, "could not open the window: {}", e),
+            Error::CreateSurface(e) => write!(f, "could not draw to the window: {}", e),
+            Error::NoAdapter => write!(f, "no graphics adapter found"),
+            Error::RequestDevice(e) => write!(f, "could not open the graphics device: {}", e),
+            Error::OutOfMemory => write!(f, "the graphics device ran out of memory"),
+            Error::Image(e) => write!(f, "could not decode an image: {}", e),
+            Error::Shader(e) => write!(f, "invalid shader: {}", e),
+            Error::BufferMap(e) => write!(f, "could not read back from the graphics device: {}", e),
+            Error::Io { path, source } => write!(f, "could not read {}: {}", path.display(), source),
+            Error::Model { path, source } => {
+                write!(f, "could not load the model {}: {}", path.display(), source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::EventLoop(e) => Some(e),
+            Error::Window(e) => Some(e),
+            Error::CreateSurface(e) => Some(e),
+            Error::RequestDevice(e) => Some(e),
+            Error::Image(e) => Some(e),
+            Error::Shader(e) => Some(e),
+            Error::BufferMap(e) => Some(e),
+            Error::Io { source, .. } => Some(source),
+            Error::Model { source, .. } => Some(source),
+            Error::NoAdapter | Error::OutOfMemory => None,
+        }
+    }
+}
+
+impl From<winit::error::EventLoopError> for Error {
+    fn from(e: winit::error::EventLoopError) -> Self {
+        Error::EventLoop(e)
+    }
+}
+
+impl From<winit::error::OsError> for Error {
+    fn from(e: winit::error::OsError) -> Self {
+        Error::Window(e)
+    }
+}
+
+impl From<wgpu::CreateSurfaceError> for Error {
+    fn from(e: wgpu::CreateSurfaceError) -> Self {
+        Error::CreateSurface(e)
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for Error {
+    fn from(e: wgpu::RequestDeviceError) -> Self {
+        Error::RequestDevice(e)
+    }
+}
+
+impl From<wgpu::BufferAsyncError> for Error {
+    fn from(e: wgpu::BufferAsyncError) -> Self {
+        Error::BufferMap(e)
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(e: image::ImageError) -> Self {
+        Error::Image(e)
+    }
+}
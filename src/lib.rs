@@ -1,15 +1,33 @@
+mod error;
 mod utils;
 mod world;
 
+pub use error::{Error, Result};
+pub use utils::camera::Camera;
+pub use utils::state::State;
+pub use world::World;
+
 use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc::{self, Receiver};
 //use utils::model;
-use utils::state::State;
-use utils::{input::InputHandler, voxel_handler::VoxelHandler};
+use utils::{
+    backend::BackendOptions,
+    bindings::{Action, KeyBindings},
+    brush::Brush,
+    cutaway::Cutaway,
+    debug::DebugOverlay,
+    editor::Editor,
+    hotbar::Hotbar,
+    input::{InputHandler, InputType},
+    profiler,
+    resources::ResourcePack,
+    shader_watch::ShaderWatcher,
+    voxel_handler::VoxelHandler,
+};
 use world::chunk::CHUNK_SIZE;
 use world::{WORLD_AREA, WORLD_D, WORLD_H, WORLD_W};
 
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::time::sleep;
 
 use winit::{event_loop::EventLoop, keyboard::KeyCode, window::WindowBuilder};
@@ -19,20 +37,42 @@ struct VoxelGame {
     world: Option<world::World>,
     mobs: Vec<utils::model::Model>,
     input_handler: InputHandler,
+    /// Input the camera has not ticked with yet. Presses stay until a tick
+    /// handles them, so frames without a tick don't drop them.
+    tick_input: InputHandler,
     voxel_handler: VoxelHandler,
-    num_block: u8,
+    editor: Editor,
+    brush: Brush,
+    hotbar: Hotbar,
+    debug: DebugOverlay,
+    cutaway: Cutaway,
+    /// Mob the camera keeps centred.
+    follow: Option<usize>,
+    /// Rebuilds the pipelines when the shaders are edited, in dev mode.
+    shader_watcher: Option<ShaderWatcher>,
 }
 
 impl<'a> VoxelGame {
     fn new() -> Self {
         let mobs = vec![];
-        let input_handler = InputHandler::new();
+        let bindings = KeyBindings::load_or_default();
+        let mut input_handler = InputHandler::new();
+        input_handler.set_bindings(bindings.clone());
+        let mut tick_input = InputHandler::new();
+        tick_input.set_bindings(bindings);
         Self {
             world: None,
             mobs,
             input_handler,
+            tick_input,
             voxel_handler: VoxelHandler::new(),
-            num_block: 1,
+            editor: Editor::new(),
+            brush: Brush::new(),
+            hotbar: Hotbar::new(),
+            debug: DebugOverlay::new(),
+            cutaway: Cutaway::new(),
+            follow: None,
+            shader_watcher: ShaderWatcher::from_env(),
         }
     }
 
@@ -40,25 +80,43 @@ impl<'a> VoxelGame {
         mut game: VoxelGame,
         state: Arc<Mutex<State<'a>>>,
         mut input_rx: Receiver<Box<InputHandler>>,
-    ) {
+    ) -> Result<()> {
         let frame_duration = Duration::from_secs_f32(1.0 / FPS as f32);
+        let tick = Duration::from_secs_f32(1.0 / TICK_RATE as f32);
         let mut last_frame_time = Instant::now();
+        let mut accumulator = Duration::ZERO;
 
         loop {
             let now = Instant::now();
             let delta_time = now.duration_since(last_frame_time);
 
             while let Ok(input) = input_rx.try_recv() {
-                game.input_handler = *input;
+                game.input_handler.merge(*input);
             }
             if game.input_handler.close{
                 break;
             }
             if delta_time >= frame_duration {
-                //println!("{}", delta_time.as_millis());
+                game.debug.frame(delta_time);
+                profiler::end_frame();
+                // Drop the time a long stall left behind instead of catching up
+                accumulator = (accumulator + delta_time).min(tick * MAX_TICKS);
                 if let Ok(mut state) = state.lock() {
-                    VoxelGame::render(&mut game, &mut state);
-                    VoxelGame::update(&mut game, &mut state);
+                    let captured = game.hotbar.update(&game.input_handler, state.size);
+                    game.tick_input.merge(game.input_handler.clone());
+                    while accumulator >= tick {
+                        state.previous_camera = state.camera;
+                        VoxelGame::tick(&mut game, &mut state, captured, tick.as_secs_f32());
+                        game.tick_input.consume();
+                        accumulator -= tick;
+                    }
+                    // Input, picking and the UI run once per frame, against
+                    // the camera the frame is drawn with
+                    let alpha = accumulator.as_secs_f32() / tick.as_secs_f32();
+                    let mut camera = state.previous_camera.interpolate(&state.camera, alpha);
+                    VoxelGame::update(&mut game, &mut state, &mut camera, captured);
+                    game.input_handler.consume();
+                    VoxelGame::render(&mut game, &mut state, &mut camera)?;
                     drop(state);
                 }
 
@@ -69,22 +127,94 @@ impl<'a> VoxelGame {
                 sleep(frame_duration - elapsed).await;
             }
         }
+        Ok(())
+    }
+
+    /// Advances the simulation by one fixed step: the camera and the world.
+    fn tick(game: &mut VoxelGame, state: &mut State<'a>, captured: bool, dt: f32) {
+        state.input(&game.tick_input, captured, dt);
+        if let Some(world) = &mut game.world {
+            world.update(dt);
+        }
     }
 
-    fn update(game: &mut VoxelGame, state: &mut State<'a>) {
-        let mut camera = state.camera;
+    /// Handles the input of a frame, `camera` being the one it is drawn with.
+    fn update(game: &mut VoxelGame, state: &mut State<'a>, camera: &mut Camera, captured: bool) {
         let relation = state.camera_uniform.relation;
         let mouse_pos = game.input_handler.mouse_pos();
         let size = state.size;
-        VoxelGame::input(game);
-        state.input(&game.input_handler);
+        if game.input_handler.check(Action::ReloadBindings, InputType::Pressed) {
+            let bindings = KeyBindings::load_or_default();
+            game.input_handler.set_bindings(bindings.clone());
+            game.tick_input.set_bindings(bindings);
+            log::info!("Key bindings reloaded");
+        }
+        if game.input_handler.check(Action::Antialiasing, InputType::Pressed) {
+            state.cycle_sample_count();
+        }
+        if game.input_handler.check(Action::ReloadResources, InputType::Pressed) {
+            let shader_dir = game.shader_watcher.as_ref().map(|watcher| watcher.dir());
+            match state.reload_resources(shader_dir) {
+                Ok(()) => log::info!("Resources reloaded"),
+                Err(e) => log::error!("Could not reload the resources: {}", e),
+            }
+        }
+        if let Some(watcher) = game.shader_watcher.as_mut() {
+            if watcher.changed() {
+                match state.reload_shaders(watcher.dir()) {
+                    Ok(()) => log::info!("Shaders reloaded"),
+                    Err(e) => log::error!("Keeping the last good shaders, {}", e),
+                }
+            }
+        }
+        if game.input_handler.check(Action::Follow, InputType::Pressed) {
+            game.follow = match game.follow {
+                None if !game.mobs.is_empty() => Some(0),
+                Some(i) if i + 1 < game.mobs.len() => Some(i + 1),
+                _ => None,
+            };
+            if game.follow.is_none() {
+                state.camera.focus = None;
+            }
+        }
+        if let Some(mob) = game.follow.and_then(|i| game.mobs.get(i)) {
+            state.camera.focus_on(mob.position.into());
+        }
+        game.debug.update(&game.input_handler);
         let mut voxel_handler = game.voxel_handler.clone();
         if let Some(world) = &mut game.world {
-            voxel_handler.update(&mut camera, mouse_pos, size, relation, world);
+            game.cutaway.update(&game.input_handler, world, state);
+            voxel_handler.update(camera, mouse_pos, size, relation, world);
+            if !captured && game.input_handler.check(Action::Focus, InputType::DoublePressed) {
+                if let Some([x, y, z]) = voxel_handler.voxel_world_pos {
+                    let voxel = [x.floor() as i32, y.floor() as i32, z.floor() as i32];
+                    state.camera.focus_on(world::World::voxel_center(voxel));
+                    game.follow = None;
+                }
+            }
+            let mut edited = captured;
+            if !captured {
+                let pasted = game.editor.update(
+                    &game.input_handler,
+                    voxel_handler.voxel_world_pos,
+                    world,
+                );
+                let brushed = game.brush.update(
+                    &game.input_handler,
+                    voxel_handler.voxel_world_pos,
+                    world,
+                    game.hotbar.block(),
+                );
+                // Edits already rebuilt their chunks, nothing to restore
+                if pasted || brushed {
+                    voxel_handler.last_position = None;
+                    edited = true;
+                }
+            }
 
             if let Some(index) = voxel_handler.chunk_index {
                 let select = voxel_handler.select_voxel(world);
-                world.chunks[index].reflesh(&state.queue, &world.voxels, Some(select));
+                world.chunks[index].reflesh(&world.voxels, Some(select));
 
                 if let Some(_) = voxel_handler.last_world {
                     let [x, y, z] = voxel_handler.last_world.unwrap();
@@ -113,21 +243,19 @@ impl<'a> VoxelGame {
                                 voxel_handler.last_state.unwrap();
                         }
 
-                        world.chunks[chunk_index].reflesh(&state.queue, &world.voxels, None);
+                        world.chunks[chunk_index].reflesh(&world.voxels, None);
                     }
                 }
 
-                if game.input_handler.check_mouse(
-                    winit::event::MouseButton::Left,
-                    utils::input::InputType::Pressed,
-                ) {
+                if !edited
+                    && game.input_handler.check(Action::RemoveBlock, InputType::Pressed)
+                {
                     voxel_handler.change_voxel(world, 0);
                     voxel_handler.last_state = Some(0);
-                } else if game.input_handler.check_mouse(
-                    winit::event::MouseButton::Right,
-                    utils::input::InputType::Pressed,
-                ) {
-                    voxel_handler.add_voxel(world, game.num_block);
+                } else if !edited
+                    && game.input_handler.check(Action::PlaceBlock, InputType::Pressed)
+                {
+                    voxel_handler.add_voxel(world, game.hotbar.block());
                 }
                 if voxel_handler.last_state == Some(0) {
                     let w_pos = voxel_handler.voxel_world_pos.unwrap();
@@ -147,7 +275,7 @@ impl<'a> VoxelGame {
                             chunk_index = cx as usize
                                 + WORLD_D as usize * cz as usize
                                 + WORLD_AREA as usize * cy as usize;
-                            world.chunks[chunk_index].reflesh(&state.queue, &world.voxels, None);
+                            world.chunks[chunk_index].reflesh(&world.voxels, None);
                         }
                     } else if (x + 1.0) % CHUNK_SIZE as f32 - 1.0 == 0.0 {
                         if cx < WORLD_W {
@@ -155,7 +283,7 @@ impl<'a> VoxelGame {
                             chunk_index = cx as usize
                                 + WORLD_D as usize * cz as usize
                                 + WORLD_AREA as usize * cy as usize;
-                            world.chunks[chunk_index].reflesh(&state.queue, &world.voxels, None);
+                            world.chunks[chunk_index].reflesh(&world.voxels, None);
                         }
                     }
 
@@ -165,7 +293,7 @@ impl<'a> VoxelGame {
                             chunk_index = cx as usize
                                 + WORLD_D as usize * cz as usize
                                 + WORLD_AREA as usize * cy as usize;
-                            world.chunks[chunk_index].reflesh(&state.queue, &world.voxels, None);
+                            world.chunks[chunk_index].reflesh(&world.voxels, None);
                         }
                     } else if (y + 1.0) % CHUNK_SIZE as f32 == 0.0 {
                         if cy < WORLD_H {
@@ -173,7 +301,7 @@ impl<'a> VoxelGame {
                             chunk_index = cx as usize
                                 + WORLD_D as usize * cz as usize
                                 + WORLD_AREA as usize * cy as usize;
-                            world.chunks[chunk_index].reflesh(&state.queue, &world.voxels, None);
+                            world.chunks[chunk_index].reflesh(&world.voxels, None);
                         }
                     }
 
@@ -183,7 +311,7 @@ impl<'a> VoxelGame {
                             chunk_index = cx as usize
                                 + WORLD_D as usize * cz as usize
                                 + WORLD_AREA as usize * cy as usize;
-                            world.chunks[chunk_index].reflesh(&state.queue, &world.voxels, None);
+                            world.chunks[chunk_index].reflesh(&world.voxels, None);
                         }
                     } else if (z + 1.0) % CHUNK_SIZE as f32 == 0.0 {
                         if cz < WORLD_W {
@@ -191,79 +319,74 @@ impl<'a> VoxelGame {
                             chunk_index = cx as usize
                                 + WORLD_D as usize * cz as usize
                                 + WORLD_AREA as usize * cy as usize;
-                            world.chunks[chunk_index].reflesh(&state.queue, &world.voxels, None);
+                            world.chunks[chunk_index].reflesh(&world.voxels, None);
                         }
                     }
                 }
             }
-            world.update();
+            if game.input_handler.check(Action::Screenshot, InputType::Pressed) {
+                let time = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+                let path = format!("screenshot_{}.png", time);
+                match state.render_to_image(world, camera) {
+                    Ok(image) => match image.save(&path) {
+                        Ok(()) => log::info!("Saved {}", path),
+                        Err(e) => log::error!("Could not save {}: {}", path, e),
+                    },
+                    Err(e) => log::error!("Could not take a screenshot: {}", e),
+                }
+            }
+
+            state.ui.clear();
+            state.ui.hotbar(&game.hotbar, size);
+            if game.debug.visible {
+                let lines = game.debug.lines(
+                    camera,
+                    voxel_handler.voxel_world_pos,
+                    world,
+                    game.hotbar.block(),
+                    state.chunk_draw_calls(),
+                );
+                state.ui.text(&lines, [8.0, 8.0], 2.0, size);
+            }
+            game.debug.ui_vertices = state.ui.vertex_count();
+            state.ui.upload(&state.queue);
         }
         game.voxel_handler = voxel_handler;
     }
 
-    fn input(game: &mut VoxelGame){
-        if game.input_handler.check_key(KeyCode::Digit0, utils::input::InputType::Pressed){
-            game.num_block = 1;
-        }
-        if game.input_handler.check_key(KeyCode::Digit1, utils::input::InputType::Pressed){
-            game.num_block = 2;
-        }
-        if game.input_handler.check_key(KeyCode::Digit2, utils::input::InputType::Pressed){
-            game.num_block = 3;
-        }
-        if game.input_handler.check_key(KeyCode::Digit3, utils::input::InputType::Pressed){
-            game.num_block = 4;
+    fn render(game: &mut VoxelGame, state: &mut State<'a>, camera: &mut Camera) -> Result<()> {
+        if let Some(window) = state.window() {
+            window.request_redraw();
         }
-        if game.input_handler.check_key(KeyCode::Digit4, utils::input::InputType::Pressed){
-            game.num_block = 5;
-        }
-        if game.input_handler.check_key(KeyCode::Digit5, utils::input::InputType::Pressed){
-            game.num_block = 6;
-        }
-        if game.input_handler.check_key(KeyCode::Digit6, utils::input::InputType::Pressed){
-            game.num_block = 7;
-        }
-        
-    }
-
-    fn render(game: &mut VoxelGame, state: &mut State<'a>) {
-        state.window().request_redraw();
         let mobs = &game.mobs;
         let size = state.size;
-        let camera_m = state.camera.build_view_projection_matrix();
-        match &game.world {
+        state.write_camera(camera);
+        match &mut game.world {
             None => {}
             Some(world) => {
-                match state.render(mobs, world, camera_m, state.camera_uniform.relation) {
+                world.update_lods(&state.camera_uniform, size.width, size.height);
+                match state.render(mobs, world) {
                     Ok(_) => {}
                     // Reconfigure the surface if it's lost or outdated
                     Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
                         state.resize(size)
                     }
                     // The system is out of memory, we should probably quit
-                    Err(wgpu::SurfaceError::OutOfMemory) => {
-                        log::error!("OutOfMemory");
-                        panic!("Se te acabo la memoria mi rey");
-                    }
+                    Err(wgpu::SurfaceError::OutOfMemory) => return Err(Error::OutOfMemory),
 
                     // This happens when the a frame takes too long to present
                     Err(wgpu::SurfaceError::Timeout) => {
-                        panic!("Eres muy lento mi rey")
+                        log::warn!("Frame timed out, skipping it");
                     }
                 }
             }
         }
+        Ok(())
     }
 
-    fn start_world(&mut self, state: &State<'a>) {
+    fn start_world(&mut self) {
         let mut world = world::World::new();
-        let bytes = include_bytes!("assets/tex_array_0.png");
-        world.build_chunk(
-            &state.device,
-            bytes,
-            &state.texture_bind_group_layout,
-            &state.queue,
-        );
+        world.build_chunk();
         self.world = Some(world);
     }
 }
@@ -278,29 +401,47 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
 
 //Game
 const FPS: u64 = 120;
+/// Simulation steps per second, independent of the frame rate.
+const TICK_RATE: u64 = 60;
+/// Most ticks run in a frame before the simulation slows down.
+const MAX_TICKS: u32 = 8;
+pub const TERRAIN_TEXTURE: &[u8] = include_bytes!("assets/tex_array_0.png");
 
-pub async fn run() {
-    let event_loop = EventLoop::new().unwrap();
+pub async fn run() -> Result<()> {
+    let event_loop = EventLoop::new()?;
     let mut input = WinitInputHelper::new();
-    let window = WindowBuilder::new().build(&event_loop).unwrap();
+    // Sees every event, the helper only reports a few keys
+    let mut tracker = InputHandler::new();
+    let window = WindowBuilder::new().build(&event_loop)?;
     window.set_title("Isocraft");
-    let state = State::new(Arc::new(window)).await;
+    profiler::enable_from_env();
+    let state = State::new(
+        Arc::new(window),
+        BackendOptions::from_env(),
+        ResourcePack::from_env(),
+    )
+    .await?;
     let mut game = VoxelGame::new();
-    game.start_world(&state);
+    game.start_world();
     let (input_tx, input_rx) = mpsc::channel::<Box<InputHandler>>(32);
     let state = Arc::new(Mutex::new(state));
     let state_copy = Arc::clone(&state);
-    let render_thread =
-        tokio::spawn(async move { VoxelGame::run(game, state_copy, input_rx).await });
+    let render_thread = tokio::spawn(async move {
+        if let Err(e) = VoxelGame::run(game, state_copy, input_rx).await {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    });
 
     event_loop
         .run(|event, control_flow| {
+            tracker.event(&event);
             if input.update(&event) {
-                let mut input_handler = InputHandler::new();
-                input_handler.update(&input);
-                if let Err(e) = input_tx.try_send(Box::new(input_handler)) {
+                tracker.update(&input);
+                if let Err(e) = input_tx.try_send(Box::new(tracker.clone())) {
                     eprintln!("{}", e);
                 }
+                tracker.consume();
             }
             if input.close_requested() || input.key_pressed(KeyCode::Escape) || input.destroyed() {
                 let mut input_handler = InputHandler::new();
@@ -317,6 +458,6 @@ pub async fn run() {
                     state.resize(physical_size);
                 }
             };
-        })
-        .unwrap();
+        })?;
+    Ok(())
 }
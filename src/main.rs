@@ -2,5 +2,9 @@ use camera::run;
 
 #[tokio::main]
 async fn main() {
-    run().await
+    env_logger::init();
+    if let Err(e) = run().await {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
 }
@@ -2,25 +2,22 @@
 
 struct CameraUniform {
     view_proj: mat4x4<f32>,
-    _padding: vec2<f32>,
+    cut: f32,
+    _padding: f32,
     relation: vec2<f32>
 }
 
-struct UVs {
-    uv_coords: vec2<f32>,
-    uv_index: u32,
-}
-
 struct Uniforms {
-    uvs: array<UVs, 12>,
-    m_matrix: mat4x4<f32>,
+    offset: vec3<f32>,
+    opacity: f32,
 }
 
 @group(1) @binding(0)
 var<uniform> camera: CameraUniform;
 
+// One record per mesh of the batch, picked by the draw's instance
 @group(2) @binding(0)
-var<uniform> uniforms: Uniforms;
+var<storage, read> chunks: array<Uniforms>;
 
 struct VertexInput {
     @location(0) data: u32,
@@ -32,6 +29,8 @@ struct VertexOutput {
     @location(1) coords_uv: vec2<f32>,
     @location(2) shading: f32,
     @location(3) opacity: f32,
+    @location(4) height: f32,
+    @location(5) @interpolate(flat) layer: u32,
 };
 
 const face_shading: array<f32, 6> = array<f32, 6>(
@@ -121,11 +120,34 @@ fn unpack(data: u32) -> array<u32, 7>{
     return array<u32, 7>(x, y, z, voxel_id, face_id, shading_id, padding);
 }
 
-const states: f32 = 8.0;
+// Texture layers per voxel id: top, side and bottom
+const face_layers: u32 = 3u;
+
+// Corner of the texture each of the six vertices of a face takes, for even
+// and odd faces
+const uv_corners: array<u32, 12> = array<u32, 12>(
+    1u, 0u, 2u, 1u, 2u, 3u,
+    3u, 0u, 2u, 3u, 1u, 0u
+);
+
+const uv_coords: array<vec2<f32>, 4> = array<vec2<f32>, 4>(
+    vec2<f32>(0.0, 0.0),
+    vec2<f32>(0.0, 1.0),
+    vec2<f32>(1.0, 0.0),
+    vec2<f32>(1.0, 1.0)
+);
+
+fn face_uv(index: u32) -> vec2<f32> {
+    // Constant arrays can only be indexed dynamically once copied
+    var corners = uv_corners;
+    var coords = uv_coords;
+    return coords[corners[index]];
+}
 
 @vertex
 fn vs_main(
     @builtin(vertex_index) vertexIndex: u32,
+    @builtin(instance_index) instance: u32,
     model: VertexInput,
 ) -> VertexOutput {
     let data = unpack(model.data);
@@ -135,19 +157,23 @@ fn vs_main(
     let shading_id = data[5];
     let select = bool(data[6]);
     var out: VertexOutput;
-    let pos =  camera.view_proj * uniforms.m_matrix * vec4<f32>(position, 1.0);
+    let world = vec4<f32>(position + chunks[instance].offset, 1.0);
+    let pos =  camera.view_proj * world;
     let color = vec3<f32>(hash32(f32(voxel_id)));
     let uv_index: u32 = vertexIndex % 6 + (face_id & 1) * 6;
-    out.coords_uv = uniforms.uvs[uniforms.uvs[uv_index].uv_index].uv_coords;
+    out.coords_uv = face_uv(uv_index);
     out.coords_uv.y = 1.0 - out.coords_uv.y;
-    if face_id == 0{
-        out.coords_uv = vec2<f32>(2.0/3.0 + out.coords_uv.x / 3.0, f32(voxel_id) / states + out.coords_uv.y / states);
-    }else {
-        out.coords_uv = vec2<f32>(1.0/3.0 + out.coords_uv.x / 3.0, f32(voxel_id) / states + out.coords_uv.y / states);
+    var face_layer = 1u;
+    if face_id == 0u {
+        face_layer = 0u;
+    } else if face_id == 1u {
+        face_layer = 2u;
     }
+    out.layer = voxel_id * face_layers + face_layer;
     out.color = color;
     out.shading = face_shading_const(face_id) * ao_const(shading_id);
-    out.opacity = 1.0;
+    out.opacity = chunks[instance].opacity;
+    out.height = world.y;
     if select{
         out.shading *= 0.0;
     }
@@ -158,7 +184,7 @@ fn vs_main(
 // Fragment shader
 
 @group(0) @binding(0)
-var t_diffuse: texture_2d<f32>;
+var t_diffuse: texture_2d_array<f32>;
 @group(0)@binding(1)
 var s_diffuse: sampler;
 
@@ -168,8 +194,12 @@ const inv_gamma: vec3<f32> = vec3<f32>(1 / gamma);
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Cutaway view
+    if in.height > camera.cut + 0.0001 {
+        discard;
+    }
     var color: vec3<f32>;
-    color = textureSample(t_diffuse, s_diffuse, in.coords_uv).xyz;
+    color = textureSample(t_diffuse, s_diffuse, in.coords_uv, in.layer).xyz;
     //color = pow(color, gamma);
 
     //color = in.color * color;
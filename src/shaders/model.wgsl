@@ -2,7 +2,8 @@
 
 struct CameraUniform {
     view_proj: mat4x4<f32>,
-    _padding: vec2<f32>,
+    cut: f32,
+    _padding: f32,
     relation: vec2<f32>
 }
 
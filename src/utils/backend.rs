@@ -0,0 +1,120 @@
+use std::env;
+
+/// Environment variable naming the backends to try, e.g. `gl` or `vulkan`.
+const BACKEND_VAR: &str = "ISOCRAFT_BACKEND";
+/// Environment variable that, when set, asks for the software adapter.
+const FALLBACK_VAR: &str = "ISOCRAFT_FALLBACK_ADAPTER";
+/// Environment variable with the samples per pixel, 1 turning MSAA off.
+const MSAA_VAR: &str = "ISOCRAFT_MSAA";
+/// Samples per pixel when none are asked for, MSAA being opt-in.
+const DEFAULT_SAMPLE_COUNT: u32 = 1;
+
+/// Which graphics APIs and adapters the renderer may use, and how many
+/// samples it takes per pixel. Read from the command line, `--backend
+/// <name>`, `--fallback-adapter` and `--msaa <samples>`, or from the
+/// environment, the command line winning.
+#[derive(Clone, Copy, Debug)]
+pub struct BackendOptions {
+    pub backends: wgpu::Backends,
+    /// Only accept the software adapter, for machines without a GPU.
+    pub force_fallback_adapter: bool,
+    /// Lowered to the closest count the adapter supports.
+    pub sample_count: u32,
+}
+
+impl BackendOptions {
+    pub fn new() -> Self {
+        Self {
+            backends: wgpu::Backends::all(),
+            force_fallback_adapter: false,
+            sample_count: DEFAULT_SAMPLE_COUNT,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let mut options = Self::new();
+        if let Ok(name) = env::var(BACKEND_VAR) {
+            options.set_backends(&name);
+        }
+        if env::var_os(FALLBACK_VAR).is_some() {
+            options.force_fallback_adapter = true;
+        }
+        if let Ok(count) = env::var(MSAA_VAR) {
+            options.set_sample_count(&count);
+        }
+
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--backend" => match args.next() {
+                    Some(name) => options.set_backends(&name),
+                    None => log::warn!("--backend needs a name"),
+                },
+                "--fallback-adapter" => options.force_fallback_adapter = true,
+                "--msaa" => match args.next() {
+                    Some(count) => options.set_sample_count(&count),
+                    None => log::warn!("--msaa needs a sample count"),
+                },
+                _ => {}
+            }
+        }
+        options
+    }
+
+    fn set_backends(&mut self, name: &str) {
+        match parse_backends(name) {
+            Some(backends) => self.backends = backends,
+            None => log::warn!(
+                "Unknown backend {}, expected vulkan, gl, metal, dx12 or all",
+                name
+            ),
+        }
+    }
+
+    fn set_sample_count(&mut self, count: &str) {
+        match count.trim().parse() {
+            Ok(count @ (1 | 2 | 4 | 8)) => self.sample_count = count,
+            _ => log::warn!("Unknown sample count {}, expected 1, 2, 4 or 8", count),
+        }
+    }
+}
+
+fn parse_backends(name: &str) -> Option<wgpu::Backends> {
+    match name.trim().to_lowercase().as_str() {
+        "vulkan" | "vk" => Some(wgpu::Backends::VULKAN),
+        "gl" | "opengl" | "gles" => Some(wgpu::Backends::GL),
+        "metal" => Some(wgpu::Backends::METAL),
+        "dx12" | "d3d12" => Some(wgpu::Backends::DX12),
+        "primary" => Some(wgpu::Backends::PRIMARY),
+        "all" => Some(wgpu::Backends::all()),
+        _ => None,
+    }
+}
+
+/// Logs why no adapter was found along with every adapter of the machine,
+/// so the user can pick a backend that works.
+pub fn report_no_adapter(options: &BackendOptions) {
+    log::error!(
+        "No adapter found for backends {:?}{}",
+        options.backends,
+        if options.force_fallback_adapter {
+            " with the fallback adapter"
+        } else {
+            ""
+        }
+    );
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    });
+    let adapters = instance.enumerate_adapters(wgpu::Backends::all());
+    if adapters.is_empty() {
+        log::error!("This machine has no adapters for any backend");
+        return;
+    }
+    log::error!("Available adapters, select one with --backend or {}:", BACKEND_VAR);
+    for adapter in adapters {
+        let info = adapter.get_info();
+        log::error!("  {:?}: {} ({:?})", info.backend, info.name, info.device_type);
+    }
+}
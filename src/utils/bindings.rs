@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::de::{value::StrDeserializer, IntoDeserializer};
+use serde::Deserialize;
+use winit::event::MouseButton;
+use winit::keyboard::KeyCode;
+
+use Action::*;
+use Binding::*;
+
+pub const BINDINGS_PATH: &str = "keybindings.cfg";
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    RotateLeft,
+    RotateRight,
+    TurnLeft,
+    TurnRight,
+    FreeRotation,
+    Perspective,
+    Focus,
+    Follow,
+    Cutaway,
+    CutUp,
+    CutDown,
+    CrossSection,
+    Screenshot,
+    Antialiasing,
+    ZoomIn,
+    ZoomOut,
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Orbit,
+    Pan,
+    RemoveBlock,
+    PlaceBlock,
+    Slot1,
+    Slot2,
+    Slot3,
+    Slot4,
+    Slot5,
+    Slot6,
+    Slot7,
+    Slot8,
+    Slot9,
+    Palette,
+    UiClick,
+    BrushMode,
+    BrushSmaller,
+    BrushBigger,
+    SelectCornerA,
+    SelectCornerB,
+    Copy,
+    PasteMode,
+    Paste,
+    RotateClipboard,
+    MirrorClipboard,
+    SaveSchematic,
+    LoadSchematic,
+    DebugOverlay,
+    ReloadBindings,
+    ReloadResources,
+    Profiler,
+    SaveTrace,
+}
+
+impl Action {
+    pub const SLOTS: [Action; 9] = [
+        Slot1, Slot2, Slot3, Slot4, Slot5, Slot6, Slot7, Slot8, Slot9,
+    ];
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+/// Name used in the bindings file and default inputs of every action.
+const DEFAULTS: [(Action, &str, &[Binding]); 52] = [
+    (RotateLeft, "rotate_left", &[Key(KeyCode::ArrowLeft)]),
+    (RotateRight, "rotate_right", &[Key(KeyCode::ArrowRight)]),
+    (TurnLeft, "turn_left", &[Key(KeyCode::KeyQ)]),
+    (TurnRight, "turn_right", &[Key(KeyCode::KeyE)]),
+    (FreeRotation, "free_rotation", &[Key(KeyCode::KeyT)]),
+    (Perspective, "perspective", &[Key(KeyCode::KeyP)]),
+    (Focus, "focus", &[Mouse(MouseButton::Middle)]),
+    (Follow, "follow", &[Key(KeyCode::KeyF)]),
+    (Cutaway, "cutaway", &[Key(KeyCode::KeyY)]),
+    (CutUp, "cut_up", &[Key(KeyCode::PageUp)]),
+    (CutDown, "cut_down", &[Key(KeyCode::PageDown)]),
+    (CrossSection, "cross_section", &[Key(KeyCode::KeyU)]),
+    (ZoomIn, "zoom_in", &[Key(KeyCode::ArrowUp)]),
+    (ZoomOut, "zoom_out", &[Key(KeyCode::ArrowDown)]),
+    (MoveUp, "move_up", &[Key(KeyCode::KeyW)]),
+    (MoveDown, "move_down", &[Key(KeyCode::KeyS)]),
+    (MoveLeft, "move_left", &[Key(KeyCode::KeyA)]),
+    (MoveRight, "move_right", &[Key(KeyCode::KeyD)]),
+    (Orbit, "orbit", &[Mouse(MouseButton::Middle)]),
+    (
+        Pan,
+        "pan",
+        &[Key(KeyCode::ShiftLeft), Key(KeyCode::ShiftRight)],
+    ),
+    (RemoveBlock, "remove_block", &[Mouse(MouseButton::Left)]),
+    (PlaceBlock, "place_block", &[Mouse(MouseButton::Right)]),
+    (Slot1, "slot_1", &[Key(KeyCode::Digit0)]),
+    (Slot2, "slot_2", &[Key(KeyCode::Digit1)]),
+    (Slot3, "slot_3", &[Key(KeyCode::Digit2)]),
+    (Slot4, "slot_4", &[Key(KeyCode::Digit3)]),
+    (Slot5, "slot_5", &[Key(KeyCode::Digit4)]),
+    (Slot6, "slot_6", &[Key(KeyCode::Digit5)]),
+    (Slot7, "slot_7", &[Key(KeyCode::Digit6)]),
+    (Slot8, "slot_8", &[Key(KeyCode::Digit7)]),
+    (Slot9, "slot_9", &[Key(KeyCode::Digit8)]),
+    (Palette, "palette", &[Key(KeyCode::Tab)]),
+    (UiClick, "ui_click", &[Mouse(MouseButton::Left)]),
+    (BrushMode, "brush_mode", &[Key(KeyCode::KeyB)]),
+    (BrushSmaller, "brush_smaller", &[Key(KeyCode::BracketLeft)]),
+    (BrushBigger, "brush_bigger", &[Key(KeyCode::BracketRight)]),
+    (SelectCornerA, "select_corner_a", &[Key(KeyCode::KeyZ)]),
+    (SelectCornerB, "select_corner_b", &[Key(KeyCode::KeyX)]),
+    (Copy, "copy", &[Key(KeyCode::KeyC)]),
+    (PasteMode, "paste_mode", &[Key(KeyCode::KeyV)]),
+    (Paste, "paste", &[Mouse(MouseButton::Left)]),
+    (RotateClipboard, "rotate_clipboard", &[Key(KeyCode::KeyR)]),
+    (MirrorClipboard, "mirror_clipboard", &[Key(KeyCode::KeyM)]),
+    (SaveSchematic, "save_schematic", &[Key(KeyCode::F5)]),
+    (LoadSchematic, "load_schematic", &[Key(KeyCode::F9)]),
+    (DebugOverlay, "debug_overlay", &[Key(KeyCode::F3)]),
+    (Screenshot, "screenshot", &[Key(KeyCode::F2)]),
+    (Antialiasing, "antialiasing", &[Key(KeyCode::F7)]),
+    (ReloadBindings, "reload_bindings", &[Key(KeyCode::F6)]),
+    (ReloadResources, "reload_resources", &[Key(KeyCode::F8)]),
+    (Profiler, "profiler", &[Key(KeyCode::F4)]),
+    (SaveTrace, "save_trace", &[Key(KeyCode::F12)]),
+];
+
+/// Inputs that trigger each action.
+///
+/// The bindings file has one `action = input, input` line per action to
+/// change, with `#` comments. Keys use winit's `KeyCode` names (`KeyA`,
+/// `ArrowLeft`, `F5`) and mouse buttons are written `MouseLeft`,
+/// `MouseRight` or `MouseMiddle`. Actions left out keep their default.
+#[derive(Clone, Debug)]
+pub struct KeyBindings {
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            bindings: DEFAULTS
+                .iter()
+                .map(|(action, _, bindings)| (*action, bindings.to_vec()))
+                .collect(),
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], |b| b.as_slice())
+    }
+
+    /// Reads the bindings file. Unknown actions or inputs are logged and
+    /// skipped, so one typo does not undo the rest of the file.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    fn parse(text: &str) -> Self {
+        let mut bindings = KeyBindings::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some((name, inputs)) = line.split_once('=') else {
+                log::warn!("Line {} of the bindings has no '='", number + 1);
+                continue;
+            };
+            let name = name.trim();
+            let Some((action, _, _)) = DEFAULTS.iter().find(|(_, n, _)| *n == name) else {
+                log::warn!("Unknown action '{}' in the bindings", name);
+                continue;
+            };
+
+            let mut list = vec![];
+            for input in inputs.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+                match parse_binding(input) {
+                    Some(binding) => list.push(binding),
+                    None => log::warn!("Unknown input '{}' for '{}'", input, name),
+                }
+            }
+            bindings.bindings.insert(*action, list);
+        }
+
+        bindings
+    }
+
+    /// Loads `BINDINGS_PATH`, keeping the defaults when there is no file.
+    pub fn load_or_default() -> Self {
+        match KeyBindings::load(BINDINGS_PATH) {
+            Ok(bindings) => bindings,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => KeyBindings::default(),
+            Err(e) => {
+                log::error!("Could not read {}: {}", BINDINGS_PATH, e);
+                KeyBindings::default()
+            }
+        }
+    }
+}
+
+fn parse_binding(name: &str) -> Option<Binding> {
+    if let Some(button) = name.strip_prefix("Mouse") {
+        let deserializer: StrDeserializer<serde::de::value::Error> = button.into_deserializer();
+        return MouseButton::deserialize(deserializer).ok().map(Mouse);
+    }
+    let deserializer: StrDeserializer<serde::de::value::Error> = name.into_deserializer();
+    KeyCode::deserialize(deserializer).ok().map(Key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inputs_are_comma_separated() {
+        let bindings = KeyBindings::parse("orbit = MouseMiddle, KeyR ,ShiftLeft\n");
+        assert_eq!(
+            bindings.get(Orbit),
+            [
+                Mouse(MouseButton::Middle),
+                Key(KeyCode::KeyR),
+                Key(KeyCode::ShiftLeft),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_inputs_and_actions_are_skipped() {
+        let bindings = KeyBindings::parse(
+            "orbit = MouseMiddle, KeyNope, MouseFourth\nfly = KeyF\nno equals sign\npan = KeyP",
+        );
+        assert_eq!(bindings.get(Orbit), [Mouse(MouseButton::Middle)]);
+        assert_eq!(bindings.get(Pan), [Key(KeyCode::KeyP)]);
+    }
+
+    #[test]
+    fn missing_lines_keep_their_default() {
+        let bindings = KeyBindings::parse("# Only the pan\npan = KeyP # moved\n\n");
+        let defaults = KeyBindings::default();
+        assert_eq!(bindings.get(Pan), [Key(KeyCode::KeyP)]);
+        for (action, _, _) in DEFAULTS.iter().filter(|(action, _, _)| *action != Pan) {
+            assert_eq!(bindings.get(*action), defaults.get(*action));
+        }
+    }
+
+    #[test]
+    fn empty_list_unbinds_the_action() {
+        let bindings = KeyBindings::parse("orbit =");
+        assert!(bindings.get(Orbit).is_empty());
+    }
+
+    #[test]
+    fn default_actions_do_not_share_inputs() {
+        // Pairs told apart by when they apply: focus is a double press, paste
+        // only clicks in paste mode and the UI takes clicks over the world
+        let shared = [
+            (Orbit, Focus),
+            (RemoveBlock, Paste),
+            (RemoveBlock, UiClick),
+            (Paste, UiClick),
+        ];
+        for (i, (a, _, a_inputs)) in DEFAULTS.iter().enumerate() {
+            for (b, _, b_inputs) in &DEFAULTS[i + 1..] {
+                if shared.contains(&(*a, *b)) || shared.contains(&(*b, *a)) {
+                    continue;
+                }
+                for input in a_inputs.iter() {
+                    let clash = format!("{:?} and {:?} share {:?}", a, b, input);
+                    assert!(!b_inputs.contains(input), "{}", clash);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_default_name_parses_back() {
+        let text: String = DEFAULTS
+            .iter()
+            .map(|(_, name, _)| format!("{} = MouseLeft\n", name))
+            .collect();
+        let bindings = KeyBindings::parse(&text);
+        for (action, _, _) in DEFAULTS {
+            assert_eq!(bindings.get(action), [Mouse(MouseButton::Left)]);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use crate::world::World;
+
+use super::bindings::Action;
+use super::input::{InputHandler, InputType};
+
+const RADIUS_MIN: i32 = 1;
+const RADIUS_MAX: i32 = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BrushMode {
+    Sphere,
+    Cylinder,
+    Flatten,
+    Smooth,
+}
+
+impl BrushMode {
+    fn next(mode: Option<BrushMode>) -> Option<BrushMode> {
+        use BrushMode::*;
+        match mode {
+            None => Some(Sphere),
+            Some(Sphere) => Some(Cylinder),
+            Some(Cylinder) => Some(Flatten),
+            Some(Flatten) => Some(Smooth),
+            Some(Smooth) => None,
+        }
+    }
+}
+
+/// Terrain sculpting with the mouse.
+///
+/// B cycles the mode (off, sphere, cylinder, flatten, smooth) and
+/// `[` / `]` change the radius. Dragging with the right button adds
+/// blocks and with the left button removes them. Flatten levels the
+/// ground to the height where the drag started.
+pub struct Brush {
+    pub mode: Option<BrushMode>,
+    pub radius: i32,
+    height: i32,
+    last_center: Option<[i32; 3]>,
+}
+
+impl Brush {
+    pub fn new() -> Self {
+        Self {
+            mode: None,
+            radius: 3,
+            height: 0,
+            last_center: None,
+        }
+    }
+
+    /// Returns true when the mouse was used by the brush.
+    pub fn update(
+        &mut self,
+        input: &InputHandler,
+        hovered: Option<[f32; 3]>,
+        world: &mut World,
+        num_block: u8,
+    ) -> bool {
+        if input.check(Action::BrushMode, InputType::Pressed) {
+            self.mode = BrushMode::next(self.mode);
+        }
+        if input.check(Action::BrushSmaller, InputType::Pressed) {
+            self.radius = RADIUS_MIN.max(self.radius - 1);
+        }
+        if input.check(Action::BrushBigger, InputType::Pressed) {
+            self.radius = RADIUS_MAX.min(self.radius + 1);
+        }
+
+        let Some(mode) = self.mode else {
+            return false;
+        };
+        let add = input.check(Action::PlaceBlock, InputType::Held);
+        let remove = input.check(Action::RemoveBlock, InputType::Held);
+        if !(add || remove) {
+            self.last_center = None;
+            return false;
+        }
+        let Some([x, y, z]) = hovered else {
+            return true;
+        };
+        let center = [x.floor() as i32, y.floor() as i32, z.floor() as i32];
+
+        if self.last_center.is_none() {
+            self.height = center[1];
+        }
+        // Only paint again once the cursor reaches another voxel
+        if self.last_center == Some(center) {
+            return true;
+        }
+        self.last_center = Some(center);
+
+        let voxel_id = if add { num_block } else { 0 };
+        let edits = match mode {
+            BrushMode::Sphere => self.sphere(center, voxel_id),
+            BrushMode::Cylinder => self.cylinder(center, voxel_id),
+            BrushMode::Flatten => self.flatten(world, center, num_block),
+            BrushMode::Smooth => self.smooth(world, center),
+        };
+        world.edit(&edits);
+        true
+    }
+
+    fn sphere(&self, center: [i32; 3], voxel_id: u8) -> Vec<([i32; 3], u8)> {
+        let r = self.radius;
+        let mut edits = vec![];
+        for dy in -r..=r {
+            for dz in -r..=r {
+                for dx in -r..=r {
+                    if dx * dx + dy * dy + dz * dz <= r * r {
+                        edits.push(([center[0] + dx, center[1] + dy, center[2] + dz], voxel_id));
+                    }
+                }
+            }
+        }
+        edits
+    }
+
+    fn cylinder(&self, center: [i32; 3], voxel_id: u8) -> Vec<([i32; 3], u8)> {
+        let r = self.radius;
+        let mut edits = vec![];
+        for dy in -r..=r {
+            for dz in -r..=r {
+                for dx in -r..=r {
+                    if dx * dx + dz * dz <= r * r {
+                        edits.push(([center[0] + dx, center[1] + dy, center[2] + dz], voxel_id));
+                    }
+                }
+            }
+        }
+        edits
+    }
+
+    /// Clears every column above the flatten height and fills the holes
+    /// below it, down to one radius of depth.
+    fn flatten(&self, world: &World, center: [i32; 3], voxel_id: u8) -> Vec<([i32; 3], u8)> {
+        let r = self.radius;
+        let mut edits = vec![];
+        for dz in -r..=r {
+            for dx in -r..=r {
+                if dx * dx + dz * dz > r * r {
+                    continue;
+                }
+                let [x, z] = [center[0] + dx, center[2] + dz];
+                for y in self.height - r..=self.height + 2 * r {
+                    let position = [x, y, z];
+                    let solid = world.get_voxel(position) != 0;
+                    if y > self.height && solid {
+                        edits.push((position, 0));
+                    } else if y <= self.height && !solid {
+                        edits.push((position, voxel_id));
+                    }
+                }
+            }
+        }
+        edits
+    }
+
+    /// Each voxel in the sphere takes the majority of its 3x3x3
+    /// neighbourhood. New voxels copy the most common neighbouring block.
+    fn smooth(&self, world: &World, center: [i32; 3]) -> Vec<([i32; 3], u8)> {
+        let r = self.radius;
+        let mut edits = vec![];
+        for dy in -r..=r {
+            for dz in -r..=r {
+                for dx in -r..=r {
+                    if dx * dx + dy * dy + dz * dz > r * r {
+                        continue;
+                    }
+                    let position = [center[0] + dx, center[1] + dy, center[2] + dz];
+                    let mut counts = [0u32; 256];
+                    let mut solid = 0;
+                    for ny in -1..=1 {
+                        for nz in -1..=1 {
+                            for nx in -1..=1 {
+                                let voxel_id = world.get_voxel([
+                                    position[0] + nx,
+                                    position[1] + ny,
+                                    position[2] + nz,
+                                ]);
+                                if voxel_id != 0 {
+                                    counts[voxel_id as usize] += 1;
+                                    solid += 1;
+                                }
+                            }
+                        }
+                    }
+
+                    let current = world.get_voxel(position);
+                    if solid >= 14 && current == 0 {
+                        let voxel_id = (1..256).max_by_key(|&i| counts[i]).unwrap_or(1) as u8;
+                        edits.push((position, voxel_id));
+                    } else if solid < 14 && current != 0 {
+                        edits.push((position, 0));
+                    }
+                }
+            }
+        }
+        edits
+    }
+}
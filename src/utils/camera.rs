@@ -1,8 +1,9 @@
 use crate::OPENGL_TO_WGPU_MATRIX;
+use super::bindings::Action;
 use super::input::{InputHandler, InputType};
 
-use cgmath::{EuclideanSpace, Vector3};
-use winit::keyboard::KeyCode;
+use cgmath::{EuclideanSpace, InnerSpace, Vector3};
+use winit::dpi::PhysicalSize;
 
 #[derive(Clone, Copy)]
 pub struct Camera {
@@ -12,36 +13,82 @@ pub struct Camera {
     pub near: f32,
     pub far: f32,
     pub eye_position: Vector3<f32>,
+    /// Arrow keys and orbit dragging turn freely instead of only by quarter turns.
+    pub free_rotation: bool,
+    pub turn: Option<Turn>,
+    /// Pixels dragged with the orbit button since the last quarter turn.
+    pub orbit_drag: f32,
+    /// Orbits the view centre in perspective instead of the isometric view.
+    pub perspective: bool,
+    /// Vertical field of view of the perspective mode, in degrees.
+    pub fov: f32,
+    /// Point in world space the view is gliding to the centre of.
+    pub focus: Option<Vector3<f32>>,
+}
+
+/// Animated quarter turn around the Y axis, in degrees.
+#[derive(Clone, Copy, Debug)]
+pub struct Turn {
+    from: f32,
+    to: f32,
+    progress: f32,
 }
 
 const SCALE_MIN: f32 = 0.1;
 const SCALE_MAX: f32 = 50.0;
-const SCALE_SPEED: f32 = 1.0;
+/// Speeds are per second.
+const SCALE_SPEED: f32 = 120.0;
 pub const ANGLES: [f32; 3] = [0.0, 0.0, 0.0];
-const ANG_SPEED: f32 = 1.0;
-const SPEED: f32 = 0.1;
+const ANG_SPEED: f32 = 120.0;
+const SPEED: f32 = 12.0;
+/// Zoom factor of one wheel notch.
+const WHEEL_ZOOM: f32 = 1.1;
+/// Degrees turned per pixel of orbit drag.
+const ORBIT_SPEED: f32 = 0.3;
+/// Angle between the canonical isometric views.
+const TURN_ANGLE: f32 = 90.0;
+/// Seconds taken by a quarter turn.
+const TURN_TIME: f32 = 0.2;
+/// Orbit drag in pixels that makes a quarter turn out of free rotation mode.
+const TURN_DRAG: f32 = 120.0;
+pub const FOV: f32 = 60.0;
+/// `CameraUniform::cut` that hides nothing.
+pub const NO_CUT: f32 = 1.0e9;
+const PERSPECTIVE_NEAR: f32 = 0.01;
+const PERSPECTIVE_FAR: f32 = 100.0;
+/// How fast the view closes in on its focus, per second.
+const FOCUS_RATE: f32 = 8.0;
+/// Half the height of the view at a scale of 1, in world units.
+const VIEW_HALF_EXTENT: f32 = 4.0;
 
 impl Camera {
-    pub fn build_view_projection_matrix(&mut self) -> cgmath::Matrix4<f32> {
+    fn eye_direction(&self) -> Vector3<f32> {
         let rotation_x = cgmath::Matrix3::from_angle_x(cgmath::Deg(self.ang[0]));
         let rotation_y = cgmath::Matrix3::from_angle_y(cgmath::Deg(self.ang[1]));
         let rotation_z = cgmath::Matrix3::from_angle_z(cgmath::Deg(self.ang[2]));
 
-        let mut camera_position = cgmath::Vector3::new(-1.0, 1.0, -1.0);
-        camera_position = rotation_x * rotation_y * rotation_z * camera_position;
+        rotation_x * rotation_y * rotation_z * cgmath::Vector3::new(-1.0, 1.0, -1.0)
+    }
+
+    pub fn build_view_projection_matrix(&mut self) -> cgmath::Matrix4<f32> {
+        let camera_position = self.eye_direction();
         self.eye_position = camera_position;
         let camera_position = cgmath::Point3::from_vec(camera_position);
 
         let look_direction = (0.0,0.0,0.0).into();
         let up_direction = cgmath::Vector3::unit_y();
 
+        if self.perspective {
+            return self.build_perspective_matrix(camera_position.to_vec(), up_direction);
+        }
+
         let view_mat = cgmath::Matrix4::look_at_rh(camera_position, look_direction, up_direction);
 
         let proj = cgmath::ortho(
-            -4.0 / self.scale + self.position[0],
-            4.0 / self.scale + self.position[0],
-            -4.0 / self.scale + self.position[1],
-            4.0 / self.scale + self.position[1],
+            -VIEW_HALF_EXTENT / self.scale + self.position[0],
+            VIEW_HALF_EXTENT / self.scale + self.position[0],
+            -VIEW_HALF_EXTENT / self.scale + self.position[1],
+            VIEW_HALF_EXTENT / self.scale + self.position[1],
             self.near,
             self.far,
         );
@@ -52,59 +99,246 @@ impl Camera {
         return OPENGL_TO_WGPU_MATRIX * proj * view_mat;
     }
 
+    /// Looks at the point the isometric view is centred on, from far enough
+    /// away that the same height is seen there at both projections.
+    fn build_perspective_matrix(
+        &self,
+        eye_direction: Vector3<f32>,
+        up_direction: Vector3<f32>,
+    ) -> cgmath::Matrix4<f32> {
+        let eye_direction = eye_direction.normalize();
+        let (right, up) = screen_axes(eye_direction);
+        let target = right * self.position[0] + up * self.position[1];
+        let distance = VIEW_HALF_EXTENT / self.scale / (self.fov.to_radians() / 2.0).tan();
+        let eye = target + eye_direction * distance;
+
+        let view_mat = cgmath::Matrix4::look_at_rh(
+            cgmath::Point3::from_vec(eye),
+            cgmath::Point3::from_vec(target),
+            up_direction,
+        );
+        let proj = cgmath::perspective(
+            cgmath::Deg(self.fov),
+            1.0,
+            PERSPECTIVE_NEAR,
+            PERSPECTIVE_FAR,
+        );
+        OPENGL_TO_WGPU_MATRIX * proj * view_mat
+    }
+
+    /// `position` that puts `point`, in world space, at the centre of the view.
+    pub fn position_of(&self, point: Vector3<f32>) -> [f32; 2] {
+        let (right, up) = screen_axes(self.eye_direction().normalize());
+        [point.dot(right), point.dot(up)]
+    }
+
+    /// Starts gliding the view to centre `point`, in world space.
+    pub fn focus_on(&mut self, point: Vector3<f32>) {
+        self.focus = Some(point);
+    }
+
+    /// Moves `position` towards the focus, which is recomputed every tick so
+    /// it stays centred while the view turns.
+    fn advance_focus(&mut self, dt: f32) {
+        let Some(point) = self.focus else {
+            return;
+        };
+        let target = self.position_of(point);
+        let t = 1.0 - (-FOCUS_RATE * dt).exp();
+        self.position[0] += (target[0] - self.position[0]) * t;
+        self.position[1] += (target[1] - self.position[1]) * t;
+        let distance = (target[0] - self.position[0]).hypot(target[1] - self.position[1]);
+        if distance * self.scale < 0.001 && self.turn.is_none() {
+            self.position = target;
+            self.focus = None;
+        }
+    }
+
+    /// Starts a quarter turn to the next canonical view, `direction` being
+    /// 1 or -1. A turn in progress is continued from its target.
+    fn start_turn(&mut self, direction: f32) {
+        let current = self.turn.map_or(self.ang[1], |turn| turn.to);
+        let steps = current / TURN_ANGLE;
+        let to = if direction > 0.0 {
+            (steps.floor() + 1.0) * TURN_ANGLE
+        } else {
+            (steps.ceil() - 1.0) * TURN_ANGLE
+        };
+        // The angle may have wrapped around since the target was chosen
+        let from = self.ang[1];
+        let distance = if direction > 0.0 {
+            (to - from).rem_euclid(360.0)
+        } else {
+            -(from - to).rem_euclid(360.0)
+        };
+        self.turn = Some(Turn {
+            from,
+            to: from + distance,
+            progress: 0.0,
+        });
+    }
+
+    /// Moves the running turn `dt` seconds forward.
+    fn advance_turn(&mut self, dt: f32) {
+        let Some(mut turn) = self.turn else {
+            return;
+        };
+        turn.progress = (turn.progress + dt / TURN_TIME).min(1.0);
+        // Ease in and out
+        let t = turn.progress;
+        let eased = t * t * (3.0 - 2.0 * t);
+        if turn.progress < 1.0 {
+            self.ang[1] = (turn.from + (turn.to - turn.from) * eased).rem_euclid(360.0);
+            self.turn = Some(turn);
+        } else {
+            // Land exactly on the view, picking compares angles exactly
+            self.ang[1] = turn.to.rem_euclid(360.0);
+            self.turn = None;
+        }
+    }
+
+    /// Distance from the centre of the view to `pixel`, in the units of
+    /// `position` at a scale of 1.
+    fn view_offset(pixel: [f32; 2], size: PhysicalSize<u32>, relation: [f32; 2]) -> [f32; 2] {
+        let extent = 2.0 * VIEW_HALF_EXTENT;
+        [
+            (pixel[0] / size.width as f32 - 0.5) / relation[0] * extent,
+            (0.5 - pixel[1] / size.height as f32) / relation[1] * extent,
+        ]
+    }
+
+    /// Q and E, or the arrow keys, turn a quarter between the isometric
+    /// views. P toggles the perspective view and T free rotation, where the
+    /// arrow keys and orbit dragging turn by any angle. WASD move, the wheel
+    /// zooms on the cursor and orbit dragging while holding pan moves the
+    /// view. `captured` skips the mouse when it is over the UI.
     pub fn controller(
         &mut self,
         input: &InputHandler,
-        camera_uniform: &mut CameraUniform,
-        queue: &mut wgpu::Queue,
-        camera_buffer: &wgpu::Buffer,
+        relation: [f32; 2],
+        size: PhysicalSize<u32>,
+        captured: bool,
+        dt: f32,
     ) {
-        if input.check_key( KeyCode::ArrowLeft, InputType::Held) {
-            self.ang[1] = (self.ang[1] + ANG_SPEED + 360.0) % 360.0;
-            camera_uniform.update_view_proj(self);
-            queue.write_buffer(camera_buffer, 0, bytemuck::cast_slice(&[*camera_uniform]))
-        } else if input.check_key( KeyCode::ArrowRight, InputType::Held) {
-            self.ang[1] = (self.ang[1] - ANG_SPEED + 360.0) % 360.0;
-            camera_uniform.update_view_proj(self);
-            queue.write_buffer(camera_buffer, 0, bytemuck::cast_slice(&[*camera_uniform]))
-        }
-        if input.check_key( KeyCode::ArrowUp, InputType::Held) {
-            let scale_step = self.scale / SCALE_MAX * SCALE_SPEED;
+        if input.check(Action::FreeRotation, InputType::Pressed) {
+            self.free_rotation = !self.free_rotation;
+        }
+        if input.check(Action::Perspective, InputType::Pressed) {
+            self.perspective = !self.perspective;
+        }
+        let snapped = !self.free_rotation;
+        if input.check(Action::TurnLeft, InputType::Pressed)
+            || (snapped && input.check(Action::RotateLeft, InputType::Pressed))
+        {
+            self.start_turn(1.0);
+        } else if input.check(Action::TurnRight, InputType::Pressed)
+            || (snapped && input.check(Action::RotateRight, InputType::Pressed))
+        {
+            self.start_turn(-1.0);
+        }
+        self.advance_turn(dt);
+        self.advance_focus(dt);
+
+        let rotate = self.free_rotation && self.turn.is_none();
+        if rotate && input.check( Action::RotateLeft, InputType::Held) {
+            self.ang[1] = (self.ang[1] + ANG_SPEED * dt + 360.0) % 360.0;
+        } else if rotate && input.check( Action::RotateRight, InputType::Held) {
+            self.ang[1] = (self.ang[1] - ANG_SPEED * dt + 360.0) % 360.0;
+        }
+        if input.check( Action::ZoomIn, InputType::Held) {
+            let scale_step = self.scale / SCALE_MAX * SCALE_SPEED * dt;
             self.scale = SCALE_MAX.min(self.scale + scale_step);
-            camera_uniform.update_view_proj(self);
-            queue.write_buffer(camera_buffer, 0, bytemuck::cast_slice(&[*camera_uniform]))
-        } else if input.check_key( KeyCode::ArrowDown, InputType::Held) {
-            let scale_step = self.scale / SCALE_MAX * SCALE_SPEED;
+        } else if input.check( Action::ZoomOut, InputType::Held) {
+            let scale_step = self.scale / SCALE_MAX * SCALE_SPEED * dt;
             self.scale = SCALE_MIN.max(self.scale - scale_step);
-            camera_uniform.update_view_proj(self);
-            queue.write_buffer(camera_buffer, 0, bytemuck::cast_slice(&[*camera_uniform]))
-        }
-        if input.check_key( KeyCode::KeyW, InputType::Held) {
-            self.position[1] += SPEED / self.scale;
-            camera_uniform.update_view_proj(self);
-            queue.write_buffer(camera_buffer, 0, bytemuck::cast_slice(&[*camera_uniform]))
-        } else if input.check_key( KeyCode::KeyS, InputType::Held) {
-            self.position[1] -= SPEED / self.scale;
-            camera_uniform.update_view_proj(self);
-            queue.write_buffer(camera_buffer, 0, bytemuck::cast_slice(&[*camera_uniform]))
-        }
-        if input.check_key( KeyCode::KeyA, InputType::Held) {
-            self.position[0] -= SPEED / self.scale;
-            camera_uniform.update_view_proj(self);
-            queue.write_buffer(camera_buffer, 0, bytemuck::cast_slice(&[*camera_uniform]))
-        } else if input.check_key( KeyCode::KeyD, InputType::Held) {
-            self.position[0] += SPEED / self.scale;
-            camera_uniform.update_view_proj(self);
-            queue.write_buffer(camera_buffer, 0, bytemuck::cast_slice(&[*camera_uniform]))
+        }
+        if input.check( Action::MoveUp, InputType::Held) {
+            self.position[1] += SPEED * dt / self.scale;
+        } else if input.check( Action::MoveDown, InputType::Held) {
+            self.position[1] -= SPEED * dt / self.scale;
+        }
+        if input.check( Action::MoveLeft, InputType::Held) {
+            self.position[0] -= SPEED * dt / self.scale;
+        } else if input.check( Action::MoveRight, InputType::Held) {
+            self.position[0] += SPEED * dt / self.scale;
+        }
+        let moving = [Action::MoveUp, Action::MoveDown, Action::MoveLeft, Action::MoveRight];
+        if moving.iter().any(|&action| input.check(action, InputType::Held)) {
+            self.focus = None;
+        }
+
+        if captured || size.width == 0 || size.height == 0 {
+            return;
+        }
+
+        let scroll = input.scroll();
+        if scroll != 0.0 {
+            // Keep the point under the cursor in place
+            let mouse = input.mouse_pos();
+            let offset = Self::view_offset([mouse.x, mouse.y], size, relation);
+            let scale = (self.scale * WHEEL_ZOOM.powf(scroll)).clamp(SCALE_MIN, SCALE_MAX);
+            self.position[0] += offset[0] * (1.0 / self.scale - 1.0 / scale);
+            self.position[1] += offset[1] * (1.0 / self.scale - 1.0 / scale);
+            self.scale = scale;
+        }
+
+        let [dx, dy] = input.mouse_delta();
+        if input.check(Action::Orbit, InputType::Held) && (dx != 0.0 || dy != 0.0) {
+            if input.check(Action::Pan, InputType::Held) {
+                let center = [size.width as f32 / 2.0, size.height as f32 / 2.0];
+                let [ox, oy] = Self::view_offset([center[0] + dx, center[1] + dy], size, relation);
+                self.position[0] -= ox / self.scale;
+                self.position[1] -= oy / self.scale;
+                self.focus = None;
+            } else if self.free_rotation {
+                self.ang[1] = (self.ang[1] - dx * ORBIT_SPEED).rem_euclid(360.0);
+            } else {
+                self.orbit_drag += dx;
+                if self.orbit_drag.abs() >= TURN_DRAG {
+                    self.start_turn(-self.orbit_drag.signum());
+                    self.orbit_drag = 0.0;
+                }
+            }
+        } else if !input.check(Action::Orbit, InputType::Held) {
+            self.orbit_drag = 0.0;
+        }
+    }
+
+    /// State between the previous tick `self` and the next one, `alpha`
+    /// going from 0 to 1. Used to draw frames that fall between ticks.
+    pub fn interpolate(&self, next: &Camera, alpha: f32) -> Camera {
+        let lerp = |a: f32, b: f32| a + (b - a) * alpha;
+        // Turn the short way across 0 and 360 degrees
+        let yaw = (next.ang[1] - self.ang[1] + 180.0).rem_euclid(360.0) - 180.0;
+        Camera {
+            scale: lerp(self.scale, next.scale),
+            position: [
+                lerp(self.position[0], next.position[0]),
+                lerp(self.position[1], next.position[1]),
+            ],
+            ang: [
+                lerp(self.ang[0], next.ang[0]),
+                (self.ang[1] + yaw * alpha).rem_euclid(360.0),
+                lerp(self.ang[2], next.ang[2]),
+            ],
+            ..*next
         }
     }
 }
 
+/// Right and up directions of the view plane seen from `eye_direction`.
+fn screen_axes(eye_direction: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    let right = (-eye_direction).cross(Vector3::unit_y()).normalize();
+    (right, right.cross(-eye_direction))
+}
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct CameraUniform {
     view_proj: [[f32; 4]; 4],
-    _padding: [f32; 2],
+    /// Height in world space above which the chunk shader hides everything.
+    pub cut: f32,
+    _padding: f32,
     pub relation: [f32; 2],
 }
 
@@ -117,7 +351,8 @@ impl CameraUniform {
         ];
         Self {
             view_proj: cgmath::Matrix4::identity().into(),
-            _padding: [0.0, 0.0],
+            cut: NO_CUT,
+            _padding: 0.0,
             relation,
         }
     }
@@ -125,4 +360,31 @@ impl CameraUniform {
     pub fn update_view_proj(&mut self, camera: &mut Camera) {
         self.view_proj = camera.build_view_projection_matrix().into();
     }
+
+    /// Pixels spanned on a `width` by `height` screen by the longest of the
+    /// three axis-aligned segments of `length` starting at `point`. Infinite
+    /// when the point is behind a perspective camera.
+    pub fn projected_size(&self, point: [f32; 3], length: f32, width: f32, height: f32) -> f32 {
+        let view_proj = cgmath::Matrix4::from(self.view_proj);
+        let project = |p: Vector3<f32>| {
+            let clip = view_proj * p.extend(1.0);
+            (clip.w > 0.0).then(|| {
+                [
+                    clip.x / clip.w * self.relation[0] * width / 2.0,
+                    clip.y / clip.w * self.relation[1] * height / 2.0,
+                ]
+            })
+        };
+        let start = Vector3::from(point);
+        let Some(origin) = project(start) else {
+            return f32::INFINITY;
+        };
+        [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()]
+            .into_iter()
+            .map(|axis| match project(start + axis * length) {
+                Some([x, y]) => (x - origin[0]).hypot(y - origin[1]),
+                None => f32::INFINITY,
+            })
+            .fold(0.0, f32::max)
+    }
 }
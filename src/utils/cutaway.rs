@@ -0,0 +1,49 @@
+use crate::world::chunk::CHUNK_SIZE;
+use crate::world::{World, WORLD_H};
+
+use super::bindings::Action;
+use super::camera::NO_CUT;
+use super::input::{InputHandler, InputType};
+use super::state::State;
+
+/// Floor view that hides every voxel from a height up.
+///
+/// Y toggles the cut, Page Up and Page Down move it one voxel and U caps
+/// the sliced voxels with a darkened cross-section.
+pub struct Cutaway {
+    pub enabled: bool,
+    pub level: i32,
+}
+
+impl Cutaway {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            level: (WORLD_H * CHUNK_SIZE as u32 / 2) as i32,
+        }
+    }
+
+    pub fn update(&mut self, input: &InputHandler, world: &mut World, state: &mut State) {
+        let height = (WORLD_H * CHUNK_SIZE as u32) as i32;
+        if input.check(Action::Cutaway, InputType::Pressed) {
+            self.enabled = !self.enabled;
+        }
+        if input.check(Action::CutUp, InputType::Pressed) {
+            self.level = height.min(self.level + 1);
+        }
+        if input.check(Action::CutDown, InputType::Pressed) {
+            self.level = 1.max(self.level - 1);
+        }
+        if input.check(Action::CrossSection, InputType::Pressed) {
+            world.cross_section = !world.cross_section;
+        }
+
+        world.cut = self.enabled.then_some(self.level);
+        // The shader compares heights in world space, where a chunk is one unit
+        state.camera_uniform.cut = match world.cut {
+            Some(level) => level as f32 / CHUNK_SIZE as f32 - WORLD_H as f32,
+            None => NO_CUT,
+        };
+        world.update_section();
+    }
+}
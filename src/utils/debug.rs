@@ -0,0 +1,137 @@
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::world::block::BLOCKS;
+use crate::world::chunk::LOD_FACTORS;
+use crate::world::World;
+
+use super::bindings::Action;
+use super::camera::Camera;
+use super::input::{InputHandler, InputType};
+use super::profiler;
+
+/// Frames used to average the frame time.
+const FRAME_SAMPLES: usize = 120;
+
+/// F3 overlay with frame time, camera and world counters, and the time per
+/// frame of each profiled span while profiling is on.
+pub struct DebugOverlay {
+    pub visible: bool,
+    /// UI vertices of the last frame, overlay included, set once it is laid out.
+    pub ui_vertices: usize,
+    frame_times: VecDeque<Duration>,
+}
+
+impl DebugOverlay {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            ui_vertices: 0,
+            frame_times: VecDeque::with_capacity(FRAME_SAMPLES),
+        }
+    }
+
+    pub fn frame(&mut self, delta_time: Duration) {
+        if self.frame_times.len() == FRAME_SAMPLES {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(delta_time);
+    }
+
+    pub fn update(&mut self, input: &InputHandler) {
+        if input.check(Action::DebugOverlay, InputType::Pressed) {
+            self.visible = !self.visible;
+        }
+        if input.check(Action::Profiler, InputType::Pressed) {
+            profiler::set_enabled(!profiler::enabled());
+        }
+        if input.check(Action::SaveTrace, InputType::Pressed) {
+            let time = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+            let path = format!("trace_{}.json", time);
+            match profiler::write_trace(Path::new(&path)) {
+                Ok(()) => log::info!("Saved {}", path),
+                Err(e) => log::error!("Could not save {}: {}", path, e),
+            }
+        }
+    }
+
+    fn frame_time(&self) -> f32 {
+        if self.frame_times.is_empty() {
+            return 0.0;
+        }
+        let total: Duration = self.frame_times.iter().sum();
+        total.as_secs_f32() / self.frame_times.len() as f32
+    }
+
+    pub fn lines(
+        &self,
+        camera: &Camera,
+        hovered: Option<[f32; 3]>,
+        world: &World,
+        block: u8,
+        draw_calls: usize,
+    ) -> Vec<String> {
+        let frame_time = self.frame_time();
+        let fps = if frame_time > 0.0 {
+            1.0 / frame_time
+        } else {
+            0.0
+        };
+        let hovered = match hovered {
+            Some([x, y, z]) => format!("{} {} {}", x.floor(), y.floor(), z.floor()),
+            None => "-".to_string(),
+        };
+        let block = BLOCKS
+            .iter()
+            .find(|b| b.id == block)
+            .map_or("air", |b| b.name);
+        let (drawn, vertices) = world.draw_stats();
+
+        let mut lines = vec![
+            format!("fps: {:.0} ({:.2} ms)", fps, frame_time * 1000.0),
+            format!(
+                "camera: {} ang {:.1} {:.1} {:.1}",
+                if camera.perspective { "perspective" } else { "isometric" },
+                camera.ang[0],
+                camera.ang[1],
+                camera.ang[2]
+            ),
+            format!(
+                "scale: {:.2} position: {:.2} {:.2}",
+                camera.scale, camera.position[0], camera.position[1]
+            ),
+            format!("voxel: {}", hovered),
+            format!("block: {}", block),
+            format!(
+                "chunks: {} drawn / {} in {} draw calls",
+                drawn,
+                world.chunks.len(),
+                draw_calls
+            ),
+            // The overlay is laid out from these lines, so its count is known a frame later
+            format!("vertices: {} world, {} ui last frame", vertices, self.ui_vertices),
+            format!(
+                "lod: {}",
+                LOD_FACTORS
+                    .iter()
+                    .zip(world.lod_counts())
+                    .map(|(factor, count)| format!("{}x {}", factor, count))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        ];
+        if profiler::enabled() {
+            lines.push("profile: avg / max ms per frame".to_string());
+            for (name, average, longest) in profiler::frame_stats() {
+                lines.push(format!(
+                    "  {}: {:.2} / {:.2}",
+                    name,
+                    average.as_secs_f32() * 1000.0,
+                    longest.as_secs_f32() * 1000.0
+                ));
+            }
+        }
+        lines
+    }
+}
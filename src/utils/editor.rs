@@ -0,0 +1,138 @@
+use crate::world::chunk::{ChunkMesh, CHUNK_SIZE};
+use crate::world::schematic::Schematic;
+use crate::world::World;
+
+use super::bindings::Action;
+use super::input::{InputHandler, InputType};
+
+const SCHEMATIC_PATH: &str = "schematic.isc";
+
+/// Region selection, clipboard and paste preview.
+///
+/// Z and X mark the corners of the region under the cursor, C copies it,
+/// V toggles paste mode, R rotates and M mirrors the clipboard.
+/// F5 saves the clipboard to `schematic.isc` and F9 loads it back.
+pub struct Editor {
+    corners: [Option<[i32; 3]>; 2],
+    clipboard: Option<Schematic>,
+    pub pasting: bool,
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Self {
+            corners: [None, None],
+            clipboard: None,
+            pasting: false,
+        }
+    }
+
+    /// Returns true when the left click was used to paste.
+    pub fn update(
+        &mut self,
+        input: &InputHandler,
+        hovered: Option<[f32; 3]>,
+        world: &mut World,
+    ) -> bool {
+        let hovered = hovered.map(|[x, y, z]| [x.floor() as i32, y.floor() as i32, z.floor() as i32]);
+        let mut changed = false;
+
+        if input.check(Action::SelectCornerA, InputType::Pressed) && hovered.is_some() {
+            self.corners[0] = hovered;
+        }
+        if input.check(Action::SelectCornerB, InputType::Pressed) && hovered.is_some() {
+            self.corners[1] = hovered;
+        }
+        if input.check(Action::Copy, InputType::Pressed) {
+            if let [Some(a), Some(b)] = self.corners {
+                self.clipboard = Some(Schematic::copy(world, a, b));
+                changed = true;
+            }
+        }
+        if input.check(Action::PasteMode, InputType::Pressed) {
+            self.pasting = !self.pasting && self.clipboard.is_some();
+            changed = true;
+        }
+        if let Some(clipboard) = &mut self.clipboard {
+            if input.check(Action::RotateClipboard, InputType::Pressed) {
+                clipboard.rotate_y();
+                changed = true;
+            }
+            if input.check(Action::MirrorClipboard, InputType::Pressed) {
+                clipboard.mirror_x();
+                changed = true;
+            }
+            if input.check(Action::SaveSchematic, InputType::Pressed) {
+                if let Err(e) = clipboard.save(SCHEMATIC_PATH) {
+                    log::error!("Could not save {}: {}", SCHEMATIC_PATH, e);
+                }
+            }
+        }
+        if input.check(Action::LoadSchematic, InputType::Pressed) {
+            match Schematic::load(SCHEMATIC_PATH) {
+                Ok(schematic) => {
+                    self.clipboard = Some(schematic);
+                    changed = true;
+                }
+                Err(e) => log::error!("Could not load {}: {}", SCHEMATIC_PATH, e),
+            }
+        }
+
+        if changed {
+            self.build_ghost(world);
+        }
+
+        let Some([x, y, z]) = hovered else {
+            return false;
+        };
+        let origin = [x, y + 1, z];
+        if self.pasting {
+            for (ghost, offset) in world.ghost.iter_mut().zip(self.ghost_offsets()) {
+                ghost.set_position(ghost_position(origin, offset));
+            }
+            if input.check(Action::Paste, InputType::Pressed) {
+                if let Some(clipboard) = &self.clipboard {
+                    clipboard.paste(world, origin);
+                    world.reflesh_dirty();
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    fn ghost_offsets(&self) -> Vec<[u32; 3]> {
+        let Some(clipboard) = &self.clipboard else {
+            return vec![];
+        };
+        let size = CHUNK_SIZE as u32;
+        let mut offsets = vec![];
+        for y in (0..clipboard.size[1]).step_by(size as usize) {
+            for z in (0..clipboard.size[2]).step_by(size as usize) {
+                for x in (0..clipboard.size[0]).step_by(size as usize) {
+                    offsets.push([x, y, z]);
+                }
+            }
+        }
+        offsets
+    }
+
+    fn build_ghost(&self, world: &mut World) {
+        world.ghost.clear();
+        let Some(clipboard) = self.clipboard.as_ref().filter(|_| self.pasting) else {
+            return;
+        };
+        for offset in self.ghost_offsets() {
+            let vertex = clipboard.build_mesh(offset);
+            world.ghost.push(ChunkMesh::ghost(ghost_position([0; 3], offset), vertex));
+        }
+    }
+}
+
+fn ghost_position(origin: [i32; 3], offset: [u32; 3]) -> [f32; 3] {
+    [
+        (origin[0] + offset[0] as i32) as f32 / CHUNK_SIZE as f32,
+        (origin[1] + offset[1] as i32) as f32 / CHUNK_SIZE as f32,
+        (origin[2] + offset[2] as i32) as f32 / CHUNK_SIZE as f32,
+    ]
+}
@@ -0,0 +1,118 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::profiler::{self, Track};
+
+/// Feature the timer needs, only requested when the adapter has it.
+pub const TIMER_FEATURES: wgpu::Features = wgpu::Features::TIMESTAMP_QUERY;
+
+/// Timestamps written at the start and end of the render pass, read back a
+/// few frames later without stalling. Frames drawn while a readback is in
+/// flight are not timed.
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    read_buffer: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick.
+    period: f32,
+    /// When the timed frame was submitted, None while no readback is in flight.
+    submitted: Option<Instant>,
+    /// Set by the map callback, once `read_buffer` holds the timestamps or
+    /// could not be mapped.
+    mapped: Arc<Mutex<Option<Result<(), wgpu::BufferAsyncError>>>>,
+}
+
+impl GpuTimer {
+    /// None when the device can't write timestamps.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(TIMER_FEATURES) {
+            log::info!("Timestamp queries unavailable, the GPU won't be profiled");
+            return None;
+        }
+        let size = 2 * wgpu::QUERY_SIZE as u64;
+        Some(Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("Render pass timestamps"),
+                ty: wgpu::QueryType::Timestamp,
+                count: 2,
+            }),
+            resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Timestamp resolve buffer"),
+                size,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            read_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Timestamp read buffer"),
+                size,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            period: queue.get_timestamp_period(),
+            submitted: None,
+            mapped: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// Records the timestamps of the last timed frame once they arrived, and
+    /// returns whether this frame can be timed.
+    pub fn begin_frame(&mut self, device: &wgpu::Device) -> bool {
+        let Some(submitted) = self.submitted else {
+            return profiler::enabled();
+        };
+        device.poll(wgpu::Maintain::Poll);
+        let mapped = self.mapped.lock().ok().and_then(|mut mapped| mapped.take());
+        match mapped {
+            None => return false,
+            Some(Err(e)) => {
+                log::warn!("Could not read the GPU timestamps back: {}", e);
+                self.submitted = None;
+                return profiler::enabled();
+            }
+            Some(Ok(())) => {}
+        }
+        {
+            let data = self.read_buffer.slice(..).get_mapped_range();
+            let timestamps: &[u64] = bytemuck::cast_slice(&data);
+            let ticks = timestamps[1].saturating_sub(timestamps[0]);
+            let duration = Duration::from_nanos((ticks as f64 * self.period as f64) as u64);
+            profiler::record("gpu render pass", Track::Gpu, submitted, duration);
+        }
+        self.read_buffer.unmap();
+        self.submitted = None;
+        profiler::enabled()
+    }
+
+    pub fn pass_writes(&self) -> wgpu::RenderPassTimestampWrites<'_> {
+        wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(0),
+            end_of_pass_write_index: Some(1),
+        }
+    }
+
+    /// Copies the timestamps of the pass where they can be read.
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.resolve_query_set(&self.query_set, 0..2, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.read_buffer,
+            0,
+            self.read_buffer.size(),
+        );
+    }
+
+    /// Starts reading back the timestamps of the frame just submitted.
+    pub fn end_frame(&mut self) {
+        let mapped = self.mapped.clone();
+        self.read_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                if let Ok(mut mapped) = mapped.lock() {
+                    *mapped = Some(result);
+                }
+            });
+        self.submitted = Some(Instant::now());
+    }
+}
@@ -0,0 +1,123 @@
+use winit::dpi::PhysicalSize;
+
+use crate::world::block::BLOCKS;
+
+use super::bindings::Action;
+use super::input::{InputHandler, InputType};
+
+pub const SLOT_SIZE: f32 = 56.0;
+const MARGIN: f32 = 12.0;
+const HOTBAR_SLOTS: usize = 9;
+const PALETTE_COLUMNS: usize = 8;
+
+/// Position and size in pixels, from the top left corner of the window.
+pub type Rect = [f32; 4];
+
+/// Blocks at hand and the palette to choose them from.
+///
+/// The number keys, or the wheel over the hotbar, pick a slot. Tab opens the
+/// palette, where a click puts the block under the cursor into the selected
+/// slot.
+pub struct Hotbar {
+    pub slots: Vec<u8>,
+    pub selected: usize,
+    pub palette_open: bool,
+    pub hovered: Option<usize>,
+}
+
+impl Hotbar {
+    pub fn new() -> Self {
+        Self {
+            slots: BLOCKS.iter().take(HOTBAR_SLOTS).map(|b| b.id).collect(),
+            selected: 0,
+            palette_open: false,
+            hovered: None,
+        }
+    }
+
+    /// Block id of the selected slot.
+    pub fn block(&self) -> u8 {
+        self.slots[self.selected]
+    }
+
+    /// Returns true when the mouse is over the UI and must not reach the world.
+    pub fn update(&mut self, input: &InputHandler, size: PhysicalSize<u32>) -> bool {
+        for (slot, action) in Action::SLOTS.iter().enumerate() {
+            if slot < self.slots.len() && input.check(*action, InputType::Pressed) {
+                self.selected = slot;
+            }
+        }
+
+        if input.check(Action::Palette, InputType::Pressed) {
+            self.palette_open = !self.palette_open;
+        }
+
+        let mouse = input.mouse_pos();
+        let inside = |[x, y, w, h]: Rect| {
+            mouse.x >= x && mouse.x < x + w && mouse.y >= y && mouse.y < y + h
+        };
+        let clicked = input.check(Action::UiClick, InputType::Pressed);
+        let slots = self.slots.len();
+
+        let captured = if self.palette_open {
+            self.hovered = (0..BLOCKS.len()).find(|&i| inside(self.palette_rect(i, size)));
+            if let (Some(i), true) = (self.hovered, clicked) {
+                self.slots[self.selected] = BLOCKS[i].id;
+            }
+            true
+        } else {
+            self.hovered = None;
+            match (0..slots).find(|&i| inside(self.slot_rect(i, size))) {
+                Some(slot) => {
+                    if clicked {
+                        self.selected = slot;
+                    }
+                    true
+                }
+                None => false,
+            }
+        };
+
+        // Elsewhere the wheel zooms the camera
+        let scroll = input.scroll();
+        if captured && scroll > 0.0 {
+            self.selected = (self.selected + slots - 1) % slots;
+        } else if captured && scroll < 0.0 {
+            self.selected = (self.selected + 1) % slots;
+        }
+        captured
+    }
+
+    pub fn slot_rect(&self, slot: usize, size: PhysicalSize<u32>) -> Rect {
+        let width = self.slots.len() as f32 * SLOT_SIZE;
+        [
+            (size.width as f32 - width) / 2.0 + slot as f32 * SLOT_SIZE,
+            size.height as f32 - SLOT_SIZE - MARGIN,
+            SLOT_SIZE,
+            SLOT_SIZE,
+        ]
+    }
+
+    /// Background of the palette, holding one cell per registered block.
+    pub fn palette_panel(&self, size: PhysicalSize<u32>) -> Rect {
+        let rows = BLOCKS.len().div_ceil(PALETTE_COLUMNS);
+        let width = PALETTE_COLUMNS as f32 * SLOT_SIZE + 2.0 * MARGIN;
+        let height = rows as f32 * SLOT_SIZE + 2.0 * MARGIN;
+        [
+            (size.width as f32 - width) / 2.0,
+            (size.height as f32 - height) / 2.0,
+            width,
+            height,
+        ]
+    }
+
+    pub fn palette_rect(&self, index: usize, size: PhysicalSize<u32>) -> Rect {
+        let [x, y, _, _] = self.palette_panel(size);
+        [
+            x + MARGIN + (index % PALETTE_COLUMNS) as f32 * SLOT_SIZE,
+            y + MARGIN + (index / PALETTE_COLUMNS) as f32 * SLOT_SIZE,
+            SLOT_SIZE,
+            SLOT_SIZE,
+        ]
+    }
+}
@@ -1,114 +1,192 @@
 use winit::dpi::PhysicalPosition;
-use winit::event::MouseButton::{self, Left, Right};
-use winit::keyboard::KeyCode::{self, *};
+use winit::event::{ElementState, Event, MouseButton, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
 use winit_input_helper::WinitInputHelper;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::bindings::{Action, Binding, KeyBindings};
 
 use InputType::*;
 
-const KEYS: [(KeyCode, InputType); 15] = [
-    (ArrowLeft, Held),
-    (ArrowRight, Held),
-    (ArrowUp, Held),
-    (ArrowDown, Held),
-    (KeyA, Held),
-    (KeyD, Held),
-    (KeyW, Held),
-    (KeyS, Held),
-    (Digit0, Pressed),
-    (Digit1, Pressed),
-    (Digit2, Pressed),
-    (Digit3, Pressed),
-    (Digit4, Pressed),
-    (Digit5, Pressed),
-    (Digit6, Pressed),
-];
-
-const MOUSE: [(MouseButton, InputType); 2] = [(Left, Pressed), (Right, Pressed)];
+/// Longest time between the two presses of a double click.
+const DOUBLE_CLICK: Duration = Duration::from_millis(300);
 
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub enum InputType {
     Pressed,
     Held,
+    /// Pressed a second time shortly after the first press.
+    DoublePressed,
 }
 
+/// Snapshot of the keyboard and mouse, built from window events so any key
+/// can be queried.
 #[derive(Clone)]
 pub struct InputHandler {
-    pub keys: HashMap<(KeyCode, InputType), bool>,
     pub close: bool,
-    mouse: HashMap<(MouseButton, InputType), bool>,
+    keys_held: HashSet<KeyCode>,
+    keys_pressed: HashSet<KeyCode>,
+    mouse_held: HashSet<MouseButton>,
+    mouse_pressed: HashSet<MouseButton>,
+    double_pressed: HashSet<Binding>,
+    last_press: HashMap<Binding, Instant>,
     mouse_pos: PhysicalPosition<f32>,
+    mouse_delta: [f32; 2],
+    scroll: f32,
+    bindings: Arc<KeyBindings>,
 }
 
 impl InputHandler {
     pub fn new() -> Self {
-        let mut keys = HashMap::new();
-        let mut mouse = HashMap::new();
-        for i in KEYS {
-            keys.insert(i, false);
-        }
-        for i in MOUSE {
-            mouse.insert(i, false);
-        }
         Self {
-            keys,
             close: false,
-            mouse,
+            keys_held: HashSet::new(),
+            keys_pressed: HashSet::new(),
+            mouse_held: HashSet::new(),
+            mouse_pressed: HashSet::new(),
+            double_pressed: HashSet::new(),
+            last_press: HashMap::new(),
             mouse_pos: PhysicalPosition::new(0.0, 0.0),
+            mouse_delta: [0.0, 0.0],
+            scroll: 0.0,
+            bindings: Arc::new(KeyBindings::default()),
         }
     }
 
-    pub fn update(&mut self, input: &WinitInputHelper) {
-        for ((key, typ), value) in self.keys.iter_mut() {
-            match typ {
-                Pressed => {
-                    *value = input.key_pressed(*key);
+    pub fn set_bindings(&mut self, bindings: KeyBindings) {
+        self.bindings = Arc::new(bindings);
+    }
+
+    /// Tracks keys and buttons. Must see every event of the loop.
+    pub fn event<T>(&mut self, event: &Event<T>) {
+        let Event::WindowEvent { event, .. } = event else {
+            return;
+        };
+        match event {
+            WindowEvent::KeyboardInput { event, .. } => {
+                let PhysicalKey::Code(key) = event.physical_key else {
+                    return;
+                };
+                match event.state {
+                    ElementState::Pressed => {
+                        if !event.repeat {
+                            self.keys_pressed.insert(key);
+                            self.press(Binding::Key(key));
+                        }
+                        self.keys_held.insert(key);
+                    }
+                    ElementState::Released => {
+                        self.keys_held.remove(&key);
+                    }
+                }
+            }
+            WindowEvent::MouseInput { state, button, .. } => match state {
+                ElementState::Pressed => {
+                    self.mouse_pressed.insert(*button);
+                    self.mouse_held.insert(*button);
+                    self.press(Binding::Mouse(*button));
                 }
-                Held => {
-                    *value = input.key_held(*key);
+                ElementState::Released => {
+                    self.mouse_held.remove(button);
                 }
+            },
+            // Releases are not reported while the window is not focused
+            WindowEvent::Focused(false) => {
+                self.keys_held.clear();
+                self.mouse_held.clear();
             }
+            _ => {}
         }
+    }
 
-        for ((button, typ), value) in self.mouse.iter_mut() {
-            match typ {
-                Pressed => {
-                    *value = input.mouse_pressed(*button);
-                }
-                Held => {
-                    *value = input.mouse_held(*button);
-                }
+    fn press(&mut self, binding: Binding) {
+        let now = Instant::now();
+        match self.last_press.get(&binding) {
+            Some(last) if now.duration_since(*last) <= DOUBLE_CLICK => {
+                self.double_pressed.insert(binding);
+                // A third press starts a new double click
+                self.last_press.remove(&binding);
+            }
+            _ => {
+                self.last_press.insert(binding, now);
             }
         }
+    }
 
+    pub fn update(&mut self, input: &WinitInputHelper) {
         self.mouse_pos = input.cursor().unwrap_or((0.0, 0.0)).into();
+        let (dx, dy) = input.mouse_diff();
+        self.mouse_delta = [dx, dy];
+        self.scroll = input.scroll_diff().1;
+    }
+
+    /// Takes a newer snapshot while keeping the presses, motion and scrolling
+    /// of this one that no frame has handled yet.
+    pub fn merge(&mut self, newer: InputHandler) {
+        let mut newer = newer;
+        newer.keys_pressed.extend(&self.keys_pressed);
+        newer.mouse_pressed.extend(&self.mouse_pressed);
+        newer.double_pressed.extend(&self.double_pressed);
+        newer.mouse_delta[0] += self.mouse_delta[0];
+        newer.mouse_delta[1] += self.mouse_delta[1];
+        newer.scroll += self.scroll;
+        newer.close |= self.close;
+        newer.bindings = self.bindings.clone();
+        *self = newer;
+    }
+
+    /// Forgets one shot input once a frame has handled it, so a press is
+    /// not seen again while no new events arrive.
+    pub fn consume(&mut self) {
+        self.keys_pressed.clear();
+        self.mouse_pressed.clear();
+        self.double_pressed.clear();
+        self.mouse_delta = [0.0, 0.0];
+        self.scroll = 0.0;
+    }
+
+    /// True when any input bound to `action` is pressed or held.
+    pub fn check(&self, action: Action, typ: InputType) -> bool {
+        self.bindings
+            .get(action)
+            .iter()
+            .any(|binding| match *binding {
+                Binding::Key(key) => self.check_key(key, typ),
+                Binding::Mouse(button) => self.check_mouse(button, typ),
+            })
     }
 
     pub fn check_key(&self, key: KeyCode, typ: InputType) -> bool {
-        match self.keys.get(&(key, typ)) {
-            None => {
-                panic!("Tecla no cubierta")
-            }
-            Some(result) => {
-                return *result;
-            }
+        match typ {
+            Pressed => self.keys_pressed.contains(&key),
+            Held => self.keys_held.contains(&key),
+            DoublePressed => self.double_pressed.contains(&Binding::Key(key)),
         }
     }
 
     pub fn check_mouse(&self, button: MouseButton, typ: InputType) -> bool {
-        match self.mouse.get(&(button, typ)) {
-            None => {
-                panic!("Entrada de mouse no cubierta")
-            }
-            Some(result) => {
-                return *result;
-            }
+        match typ {
+            Pressed => self.mouse_pressed.contains(&button),
+            Held => self.mouse_held.contains(&button),
+            DoublePressed => self.double_pressed.contains(&Binding::Mouse(button)),
         }
     }
 
     pub fn mouse_pos(&self) -> PhysicalPosition<f32> {
         self.mouse_pos
     }
+
+    /// Cursor movement in pixels since the last frame.
+    pub fn mouse_delta(&self) -> [f32; 2] {
+        self.mouse_delta
+    }
+
+    /// Vertical wheel movement, positive when scrolling up.
+    pub fn scroll(&self) -> f32 {
+        self.scroll
+    }
 }
@@ -1,6 +1,18 @@
+pub mod backend;
+pub mod bindings;
+pub mod brush;
 pub mod camera;
+pub mod cutaway;
+pub mod debug;
+pub mod editor;
+pub mod gpu_timer;
+pub mod hotbar;
 pub mod model;
+pub mod profiler;
+pub mod resources;
 pub mod shader;
+pub mod shader_watch;
 pub mod state;
+pub mod ui;
 pub mod voxel_handler;
 pub mod input;
\ No newline at end of file
@@ -1,7 +1,9 @@
-use std::{io::BufReader, str::FromStr};
+use std::io::BufReader;
 
 use wgpu::util::DeviceExt;
 
+use crate::error::{Error, Result};
+
 pub const SCALE: f32 = 1.0;
 
 #[derive(Debug)]
@@ -22,8 +24,8 @@ impl Model {
         label: &str,
         position: [f32; 3],
         scale: f32,
-    ) -> Self {
-        let mut mesh = Mesh::from_file(url_model, device);
+    ) -> Result<Self> {
+        let mut mesh = Mesh::from_file(url_model, device)?;
         mesh.vertex.iter_mut().for_each(|x| {
             x.position = [
                 x.position[0] * scale + position[0] / SCALE,
@@ -33,13 +35,13 @@ impl Model {
         });
         queue.write_buffer(&mesh.vertex_buffer, 0, bytemuck::cast_slice(&mesh.vertex));
 
-        let texture = BindTexture::new(texture_bind_group_layout, bytes, device, queue, label);
-        Self {
+        let texture = BindTexture::new(texture_bind_group_layout, bytes, device, queue, label)?;
+        Ok(Self {
             mesh,
             texture,
             position,
             scale,
-        }
+        })
     }
 
     pub fn update(&mut self, queue: &wgpu::Queue){
@@ -98,23 +100,29 @@ impl Mesh {
         }
     }
 
-    pub fn from_file(url_model: &str, device: &wgpu::Device) -> Self {
+    pub fn from_file(url_model: &str, device: &wgpu::Device) -> Result<Self> {
         use obj::*;
         use std::fs::File;
 
+        let path = std::path::PathBuf::from(url_model);
         let path = if !cfg!(debug_assertions) {
-            let mut path =
-                std::env::current_exe().expect("Error para conseguir la ruta del ejecutable");
-            path.pop();
-            path.push(url_model);
-            path
+            let mut exe = std::env::current_exe().map_err(|source| Error::Io {
+                path: path.clone(),
+                source,
+            })?;
+            exe.pop();
+            exe.push(path);
+            exe
         } else {
-            std::path::PathBuf::from_str(url_model).expect("Error en la ruta")
+            path
         };
 
-        let input = BufReader::new(File::open(path).expect("Ruta no encontrada"));
-        let obj: Obj<TexturedVertex, u16> =
-            obj::load_obj(input).expect("Falla al cargar el modelo");
+        let file = File::open(&path).map_err(|source| Error::Io {
+            path: path.clone(),
+            source,
+        })?;
+        let obj: Obj<TexturedVertex, u16> = obj::load_obj(BufReader::new(file))
+            .map_err(|source| Error::Model { path, source })?;
 
         let vertex = obj
             .vertices
@@ -123,7 +131,7 @@ impl Mesh {
             .collect::<Vec<_>>();
         let indices = obj.indices;
 
-        Mesh::new(vertex, indices, device)
+        Ok(Mesh::new(vertex, indices, device))
     }
 }
 
@@ -141,10 +149,43 @@ impl BindTexture {
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         label: &str,
+    ) -> Result<Self> {
+        let texture = Texture::new(bytes, device, queue, label)?;
+        Ok(BindTexture::from_texture(texture_bind_group_layout, texture, device))
+    }
+
+    /// Block faces sliced from the terrain atlas `bytes` into a mipmapped
+    /// texture array, for a layout made from `Texture::array_desc`.
+    pub fn terrain(
+        terrain_bind_group_layout: &wgpu::BindGroupLayout,
+        bytes: &[u8],
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Self> {
+        let atlas = image::load_from_memory(bytes)?.to_rgba8();
+        let layers = crate::world::block::terrain_layers(&atlas);
+        let texture = Texture::from_layers(&layers, device, queue, "Terrain");
+        Ok(BindTexture::from_texture(terrain_bind_group_layout, texture, device))
+    }
+
+    pub fn from_image(
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        rgba: &image::RgbaImage,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        label: &str,
+    ) -> Self {
+        let texture = Texture::from_image(rgba, device, queue, label);
+        BindTexture::from_texture(texture_bind_group_layout, texture, device)
+    }
+
+    fn from_texture(
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        texture: Texture,
+        device: &wgpu::Device,
     ) -> Self {
-        let texture = Texture::new(bytes, device, queue, label);
         let diffuse_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
+            layout: texture_bind_group_layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
@@ -173,12 +214,68 @@ pub struct Texture {
 
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
-    fn new(bytes: &[u8], device: &wgpu::Device, queue: &wgpu::Queue, label: &str) -> Self {
-        let diffuse_image = image::load_from_memory(bytes).unwrap();
-        let rgba = diffuse_image.to_rgba8();
 
-        use image::GenericImageView;
-        let dimensions = diffuse_image.dimensions();
+    /// Depth buffer the size of `config`. Only with a single sample can it
+    /// also be sampled, with a comparison sampler, by passes that read the
+    /// depth of the frame. Multisampled depth is never resolved and can't be
+    /// bound, so those passes need MSAA off.
+    pub fn create_depth_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
+        // The GL backend draws nothing into multisampled depth that can be bound
+        let usage = if sample_count == 1 {
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+        } else {
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::DEPTH_FORMAT,
+            usage,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+    fn new(bytes: &[u8], device: &wgpu::Device, queue: &wgpu::Queue, label: &str) -> Result<Self> {
+        let diffuse_image = image::load_from_memory(bytes)?;
+        Ok(Texture::from_image(&diffuse_image.to_rgba8(), device, queue, label))
+    }
+
+    fn from_image(
+        rgba: &image::RgbaImage,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        label: &str,
+    ) -> Self {
+        let dimensions = rgba.dimensions();
         let size = wgpu::Extent3d {
             width: dimensions.0,
             height: dimensions.1,
@@ -203,7 +300,7 @@ impl Texture {
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
             },
-            &rgba,
+            rgba,
             wgpu::ImageDataLayout {
                 offset: 0,
                 bytes_per_row: Some(4 * dimensions.0),
@@ -230,6 +327,117 @@ impl Texture {
         }
     }
 
+    /// Texture array with a full mip chain, one layer per image. Every image
+    /// must have the size of the first.
+    fn from_layers(
+        layers: &[image::RgbaImage],
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        label: &str,
+    ) -> Self {
+        let (width, height) = layers[0].dimensions();
+        // The GL backend makes cube maps out of multiples of 6 layers
+        let mut layer_count = layers.len() as u32;
+        if layer_count.is_multiple_of(6) {
+            layer_count += 1;
+        }
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: layer_count,
+        };
+        let mip_level_count = size.max_mips(wgpu::TextureDimension::D2);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (layer, image) in layers.iter().enumerate() {
+            let mut mip = image.clone();
+            for mip_level in 0..mip_level_count {
+                if mip_level > 0 {
+                    mip = half_size(&mip);
+                }
+                let (w, h) = mip.dimensions();
+                queue.write_texture(
+                    wgpu::ImageCopyTexture {
+                        aspect: wgpu::TextureAspect::All,
+                        texture: &texture,
+                        mip_level,
+                        origin: wgpu::Origin3d {
+                            x: 0,
+                            y: 0,
+                            z: layer as u32,
+                        },
+                    },
+                    &mip,
+                    wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(4 * w),
+                        rows_per_image: Some(h),
+                    },
+                    wgpu::Extent3d {
+                        width: w,
+                        height: h,
+                        depth_or_array_layers: 1,
+                    },
+                );
+            }
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        // Pixels stay sharp up close and blend with the smaller mips far away
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    /// Layout of the terrain texture array made by `BindTexture::terrain`.
+    pub fn array_desc() -> wgpu::BindGroupLayoutDescriptor<'static> {
+        wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("terrain_bind_group_layout"),
+        }
+    }
+
     pub fn desc() -> wgpu::BindGroupLayoutDescriptor<'static>{
         wgpu::BindGroupLayoutDescriptor {
             entries: &[
@@ -256,6 +464,22 @@ impl Texture {
 
 }
 
+/// Next mip level of `image`, each pixel averaging the 2x2 pixels under it.
+fn half_size(image: &image::RgbaImage) -> image::RgbaImage {
+    let (width, height) = image.dimensions();
+    image::RgbaImage::from_fn((width / 2).max(1), (height / 2).max(1), |x, y| {
+        let mut sum = [0u32; 4];
+        for [dx, dy] in [[0, 0], [1, 0], [0, 1], [1, 1]] {
+            let pixel =
+                image.get_pixel((2 * x + dx).min(width - 1), (2 * y + dy).min(height - 1));
+            for (sum, channel) in sum.iter_mut().zip(pixel.0) {
+                *sum += channel as u32;
+            }
+        }
+        image::Rgba(sum.map(|channel| (channel / 4) as u8))
+    })
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
@@ -0,0 +1,215 @@
+use std::collections::VecDeque;
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Environment variable that starts the game with profiling on.
+const PROFILE_VAR: &str = "ISOCRAFT_PROFILE";
+/// Frames averaged in the overlay.
+const FRAME_SAMPLES: usize = 120;
+/// Spans kept for the trace, the oldest dropped first.
+const MAX_EVENTS: usize = 100_000;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static PROFILER: LazyLock<Mutex<Profiler>> = LazyLock::new(|| Mutex::new(Profiler::new()));
+
+thread_local! {
+    /// Small id of the thread in the trace.
+    static THREAD: u64 = {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    };
+}
+
+/// Where a span was measured. GPU spans are placed at the time their frame
+/// was submitted, on a track of their own.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Track {
+    Cpu,
+    Gpu,
+}
+
+struct Event {
+    name: &'static str,
+    track: Track,
+    thread: u64,
+    start: Instant,
+    duration: Duration,
+}
+
+/// Time spent per frame in each named span, for the overlay, and the
+/// latest spans, for a Chrome trace.
+struct Profiler {
+    epoch: Instant,
+    events: Vec<Event>,
+    /// Index in `events` of the oldest span once it is full.
+    next_event: usize,
+    /// Time in each span during the current frame.
+    frame: Vec<(&'static str, Duration)>,
+    /// Time in each span during the last `FRAME_SAMPLES` frames.
+    history: Vec<(&'static str, VecDeque<Duration>)>,
+}
+
+impl Profiler {
+    fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+            events: vec![],
+            next_event: 0,
+            frame: vec![],
+            history: vec![],
+        }
+    }
+
+    fn record(&mut self, event: Event) {
+        match self.frame.iter_mut().find(|(name, _)| *name == event.name) {
+            Some((_, total)) => *total += event.duration,
+            None => self.frame.push((event.name, event.duration)),
+        }
+        if self.events.len() < MAX_EVENTS {
+            self.events.push(event);
+        } else {
+            self.events[self.next_event] = event;
+            self.next_event = (self.next_event + 1) % MAX_EVENTS;
+        }
+    }
+
+    fn end_frame(&mut self) {
+        for (name, _) in &self.frame {
+            if !self.history.iter().any(|(seen, _)| seen == name) {
+                self.history
+                    .push((name, VecDeque::with_capacity(FRAME_SAMPLES)));
+            }
+        }
+        for (name, samples) in &mut self.history {
+            if samples.len() == FRAME_SAMPLES {
+                samples.pop_front();
+            }
+            let total = self.frame.iter().find(|(seen, _)| seen == name);
+            samples.push_back(total.map_or(Duration::ZERO, |(_, total)| *total));
+        }
+        self.frame.clear();
+    }
+}
+
+/// Measures the time until it is dropped.
+pub struct Span {
+    name: &'static str,
+    start: Instant,
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        record(self.name, Track::Cpu, self.start, self.start.elapsed());
+    }
+}
+
+/// Times the rest of the scope under `name` when profiling is on:
+/// `let _span = profiler::span("name");`.
+pub fn span(name: &'static str) -> Option<Span> {
+    enabled().then(|| Span {
+        name,
+        start: Instant::now(),
+    })
+}
+
+pub fn record(name: &'static str, track: Track, start: Instant, duration: Duration) {
+    if !enabled() {
+        return;
+    }
+    let thread = THREAD.with(|thread| *thread);
+    if let Ok(mut profiler) = PROFILER.lock() {
+        profiler.record(Event {
+            name,
+            track,
+            thread,
+            start,
+            duration,
+        });
+    }
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn set_enabled(enabled: bool) {
+    // Spans are timed from the epoch, which must come before the first one
+    LazyLock::force(&PROFILER);
+    ENABLED.store(enabled, Ordering::Relaxed);
+    log::info!("Profiling {}", if enabled { "on" } else { "off" });
+}
+
+/// Turns profiling on when asked for with `--profile` or the environment.
+pub fn enable_from_env() {
+    if env::var_os(PROFILE_VAR).is_some() || env::args().skip(1).any(|arg| arg == "--profile") {
+        set_enabled(true);
+    }
+}
+
+/// Closes the frame the spans recorded since the last call belong to.
+pub fn end_frame() {
+    if !enabled() {
+        return;
+    }
+    if let Ok(mut profiler) = PROFILER.lock() {
+        profiler.end_frame();
+    }
+}
+
+/// Average and longest time per frame of each span over the last frames,
+/// in the order they were first seen.
+pub fn frame_stats() -> Vec<(&'static str, Duration, Duration)> {
+    let Ok(profiler) = PROFILER.lock() else {
+        return vec![];
+    };
+    profiler
+        .history
+        .iter()
+        .map(|(name, samples)| {
+            let total: Duration = samples.iter().sum();
+            let average = total / samples.len().max(1) as u32;
+            let longest = samples.iter().copied().max().unwrap_or_default();
+            (*name, average, longest)
+        })
+        .collect()
+}
+
+/// Writes the recorded spans as a Chrome trace, to open in `about:tracing`
+/// or Perfetto.
+pub fn write_trace(path: &Path) -> io::Result<()> {
+    let json = {
+        let profiler = PROFILER
+            .lock()
+            .map_err(|_| io::Error::other("profiler lock poisoned"))?;
+        let (newer, older) = profiler.events.split_at(profiler.next_event);
+        // Names the track GPU spans are drawn on
+        let mut json = String::from(
+            r#"{"traceEvents":[{"name":"thread_name","ph":"M","pid":1,"tid":0,"args":{"name":"GPU"}}"#,
+        );
+        for event in older.iter().chain(newer) {
+            let (category, thread) = match event.track {
+                Track::Cpu => ("cpu", event.thread),
+                Track::Gpu => ("gpu", 0),
+            };
+            let start = event.start.saturating_duration_since(profiler.epoch);
+            let _ = write!(
+                json,
+                r#",{{"name":"{}","cat":"{}","ph":"X","pid":1,"tid":{},"ts":{:.3},"dur":{:.3}}}"#,
+                event.name,
+                category,
+                thread,
+                start.as_secs_f64() * 1e6,
+                event.duration.as_secs_f64() * 1e6
+            );
+        }
+        json.push_str(r#"],"displayTimeUnit":"ms"}"#);
+        json
+    };
+    fs::write(path, json)
+}
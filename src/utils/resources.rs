@@ -0,0 +1,171 @@
+use std::borrow::Cow;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use image::{DynamicImage, GenericImageView};
+
+use crate::world::block::{ATLAS_COLUMNS, ATLAS_ROWS};
+use crate::TERRAIN_TEXTURE;
+
+/// Environment variable with the directory of the resource pack.
+const PACK_VAR: &str = "ISOCRAFT_RESOURCE_PACK";
+
+/// Textures and shaders the renderer is built from. Each one is read from
+/// the pack directory when it is there and falls back to the embedded asset
+/// otherwise. A pack is laid out as
+///
+/// ```text
+/// terrain.png
+/// ui/frame.png
+/// ui/arrow.png
+/// shaders/chunk.wgsl
+/// shaders/model.wgsl
+/// shaders/ui.wgsl
+/// ```
+#[derive(Clone)]
+pub struct ResourcePack {
+    pub path: Option<PathBuf>,
+    pub terrain: Cow<'static, [u8]>,
+    pub frame: Cow<'static, [u8]>,
+    pub arrow: Cow<'static, [u8]>,
+    pub chunk_shader: Cow<'static, str>,
+    pub model_shader: Cow<'static, str>,
+    pub ui_shader: Cow<'static, str>,
+}
+
+impl ResourcePack {
+    /// Only the assets built into the executable.
+    pub fn embedded() -> Self {
+        Self {
+            path: None,
+            terrain: Cow::Borrowed(TERRAIN_TEXTURE),
+            frame: Cow::Borrowed(include_bytes!("../assets/frame.png")),
+            arrow: Cow::Borrowed(include_bytes!("../assets/arrow.png")),
+            chunk_shader: Cow::Borrowed(include_str!("../shaders/chunk.wgsl")),
+            model_shader: Cow::Borrowed(include_str!("../shaders/model.wgsl")),
+            ui_shader: Cow::Borrowed(include_str!("../shaders/ui.wgsl")),
+        }
+    }
+
+    /// The pack given with `--resource-pack <dir>` or the environment, the
+    /// command line winning.
+    pub fn from_env() -> Self {
+        let mut path = env::var_os(PACK_VAR).map(PathBuf::from);
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == "--resource-pack" {
+                match args.next() {
+                    Some(dir) => path = Some(PathBuf::from(dir)),
+                    None => log::warn!("--resource-pack needs a directory"),
+                }
+            }
+        }
+        Self::load(path)
+    }
+
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let mut pack = Self::embedded();
+        let Some(dir) = path else {
+            return pack;
+        };
+        if !dir.is_dir() {
+            log::warn!("Resource pack {} is not a directory", dir.display());
+            return pack;
+        }
+
+        if let Some(bytes) = read_image(&dir.join("terrain.png"), check_atlas) {
+            pack.terrain = Cow::Owned(bytes);
+        }
+        if let Some(bytes) = read_image(&dir.join("ui").join("frame.png"), |_| Ok(())) {
+            pack.frame = Cow::Owned(bytes);
+        }
+        if let Some(bytes) = read_image(&dir.join("ui").join("arrow.png"), |_| Ok(())) {
+            pack.arrow = Cow::Owned(bytes);
+        }
+        pack.read_shaders(&dir.join("shaders"));
+        log::info!("Loaded resource pack {}", dir.display());
+        pack.path = Some(dir);
+        pack
+    }
+
+    /// Reads the pack directory again, picking up changed files.
+    pub fn reload(&self) -> Self {
+        Self::load(self.path.clone())
+    }
+
+    /// This pack with the shaders found in `dir` in place of its own.
+    pub fn with_shaders(&self, dir: &Path) -> Self {
+        let mut pack = self.clone();
+        pack.read_shaders(dir);
+        pack
+    }
+
+    fn read_shaders(&mut self, dir: &Path) {
+        if let Some(source) = read_shader(&dir.join("chunk.wgsl")) {
+            self.chunk_shader = Cow::Owned(source);
+        }
+        if let Some(source) = read_shader(&dir.join("model.wgsl")) {
+            self.model_shader = Cow::Owned(source);
+        }
+        if let Some(source) = read_shader(&dir.join("ui.wgsl")) {
+            self.ui_shader = Cow::Owned(source);
+        }
+    }
+}
+
+/// Bytes of an image of the pack, None when it is missing, can't be decoded
+/// or fails `check`, so the embedded one is kept.
+fn read_image(
+    path: &Path,
+    check: impl FnOnce(&DynamicImage) -> Result<(), String>,
+) -> Option<Vec<u8>> {
+    let bytes = read(path)?;
+    let checked = image::load_from_memory(&bytes)
+        .map_err(|e| e.to_string())
+        .and_then(|image| check(&image));
+    match checked {
+        Ok(()) => Some(bytes),
+        Err(e) => {
+            log::warn!("Ignoring {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// The terrain atlas must split into whole tiles of the block grid, or its
+/// layers would be empty or lose pixels.
+fn check_atlas(image: &DynamicImage) -> Result<(), String> {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 || width % ATLAS_COLUMNS != 0 || height % ATLAS_ROWS != 0 {
+        return Err(format!(
+            "the atlas is {}x{}, not a grid of {}x{} tiles",
+            width, height, ATLAS_COLUMNS, ATLAS_ROWS
+        ));
+    }
+    Ok(())
+}
+
+fn read_shader(path: &Path) -> Option<String> {
+    let bytes = read(path)?;
+    match String::from_utf8(bytes) {
+        Ok(source) => Some(source),
+        Err(e) => {
+            log::warn!("Ignoring {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+fn read(path: &Path) -> Option<Vec<u8>> {
+    if !path.exists() {
+        return None;
+    }
+    match fs::read(path) {
+        Ok(bytes) => Some(bytes),
+        Err(e) => {
+            log::warn!("Could not read {}: {}", path.display(), e);
+            None
+        }
+    }
+}
@@ -1,27 +1,49 @@
 
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+
+use super::resources::ResourcePack;
+use crate::error::{Error, Result};
+
 pub struct Shader {
     pub shader_model: wgpu::ShaderModule,
     pub shader_chunk: wgpu::ShaderModule,
+    pub shader_ui: wgpu::ShaderModule,
     pub render_pipeline_model: wgpu::RenderPipeline,
     pub render_pipeline_chunk: wgpu::RenderPipeline,
+    pub render_pipeline_ui: wgpu::RenderPipeline,
 }
 
 impl Shader {
+    /// Compiles the shaders of `resources` into pipelines. Validation errors
+    /// are returned rather than raised on the device, so a broken shader can
+    /// be reported while the previous pipelines keep drawing.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         device: &wgpu::Device,
         texture_bind_group_layout: &wgpu::BindGroupLayout,
+        terrain_bind_group_layout: &wgpu::BindGroupLayout,
         camera_bind_group_layout: &wgpu::BindGroupLayout,
         chunk_bind_group_layout: &wgpu::BindGroupLayout,
         surface_format: wgpu::TextureFormat,
-    ) -> Self {
+        sample_count: u32,
+        resources: &ResourcePack,
+    ) -> Result<Self> {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
         let shader_model = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/model.wgsl").into()),
+            source: wgpu::ShaderSource::Wgsl(resources.model_shader.as_ref().into()),
         });
 
         let shader_chunk = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/chunk.wgsl").into()),
+            source: wgpu::ShaderSource::Wgsl(resources.chunk_shader.as_ref().into()),
+        });
+
+        let shader_ui = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shader"),
+            source: wgpu::ShaderSource::Wgsl(resources.ui_shader.as_ref().into()),
         });
 
         let render_pipeline_layout_model =
@@ -34,7 +56,14 @@ impl Shader {
             let render_pipeline_layout_chunk =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[texture_bind_group_layout, camera_bind_group_layout, chunk_bind_group_layout],
+                bind_group_layouts: &[terrain_bind_group_layout, camera_bind_group_layout, chunk_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let render_pipeline_layout_ui =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Render Pipeline Layout"),
+                bind_group_layouts: &[texture_bind_group_layout],
                 push_constant_ranges: &[],
             });
 
@@ -78,7 +107,7 @@ impl Shader {
                     bias: wgpu::DepthBiasState::default(),
                 }),
                 multisample: wgpu::MultisampleState {
-                    count: 1,
+                    count: sample_count,
                     mask: !0,
                     alpha_to_coverage_enabled: false,
                 },
@@ -125,18 +154,78 @@ impl Shader {
                     bias: wgpu::DepthBiasState::default(),
                 }),
                 multisample: wgpu::MultisampleState {
-                    count: 1,
+                    count: sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None,
+            });
+        // Drawn last in the same pass, over everything and without touching depth
+        let render_pipeline_ui =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("UI Pipeline"),
+                layout: Some(&render_pipeline_layout_ui),
+                vertex: wgpu::VertexState {
+                    module: &shader_ui,
+                    entry_point: "vs_main",
+                    buffers: &[super::ui::UiVertex::desc()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader_ui,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: surface_format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: super::model::Texture::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
                     mask: !0,
                     alpha_to_coverage_enabled: false,
                 },
                 multiview: None,
                 cache: None,
             });
-        Self {
+        if let Some(e) = pop_error_scope(device) {
+            return Err(Error::Shader(e));
+        }
+        Ok(Self {
             shader_model,
             shader_chunk,
+            shader_ui,
             render_pipeline_model,
             render_pipeline_chunk,
-        }
+            render_pipeline_ui,
+        })
+    }
+}
+
+/// The error caught by the innermost scope. Native devices report it as soon
+/// as the scope is popped, so the future is polled once instead of awaited.
+fn pop_error_scope(device: &wgpu::Device) -> Option<wgpu::Error> {
+    let mut error = pin!(device.pop_error_scope());
+    match error.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+        Poll::Ready(error) => error,
+        Poll::Pending => None,
     }
 }
@@ -0,0 +1,91 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Environment variable that turns on watching the shaders, set to `1` for
+/// the source tree or to the directory to watch.
+const WATCH_VAR: &str = "ISOCRAFT_WATCH_SHADERS";
+/// How often the directory is looked at.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Development mode noticing edits to the WGSL files of a directory, by
+/// default `src/shaders` of the source tree, so the pipelines can be rebuilt
+/// while the game runs.
+pub struct ShaderWatcher {
+    dir: PathBuf,
+    /// Latest modification of a shader, None until the first poll.
+    modified: Option<SystemTime>,
+    last_poll: Instant,
+}
+
+impl ShaderWatcher {
+    pub fn new(dir: PathBuf) -> Self {
+        if dir.is_dir() {
+            log::info!("Watching shaders in {}", dir.display());
+        } else {
+            log::warn!("Can't watch shaders in {}, not a directory", dir.display());
+        }
+        Self {
+            dir,
+            modified: None,
+            last_poll: Instant::now() - POLL_INTERVAL,
+        }
+    }
+
+    /// A watcher when asked for with `--watch-shaders [dir]` or the
+    /// environment, the command line winning.
+    pub fn from_env() -> Option<Self> {
+        let mut dir = env::var_os(WATCH_VAR).map(|dir| {
+            if dir.is_empty() || dir == "1" {
+                default_dir()
+            } else {
+                PathBuf::from(dir)
+            }
+        });
+        let mut args = env::args().skip(1).peekable();
+        while let Some(arg) = args.next() {
+            if arg == "--watch-shaders" {
+                dir = match args.next_if(|next| !next.starts_with("--")) {
+                    Some(next) => Some(PathBuf::from(next)),
+                    None => Some(default_dir()),
+                };
+            }
+        }
+        dir.map(Self::new)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// True when a shader changed since the last time it returned true, and
+    /// on the first poll so the watched sources replace the loaded ones.
+    pub fn changed(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        let Some(modified) = latest_modification(&self.dir) else {
+            return false;
+        };
+        if self.modified == Some(modified) {
+            return false;
+        }
+        self.modified = Some(modified);
+        true
+    }
+}
+
+fn default_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("src").join("shaders")
+}
+
+fn latest_modification(dir: &Path) -> Option<SystemTime> {
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "wgsl"))
+        .filter_map(|entry| entry.metadata().and_then(|meta| meta.modified()).ok())
+        .max()
+}
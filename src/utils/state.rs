@@ -1,64 +1,106 @@
 use std::iter;
+use std::path::Path;
 use std::sync::Arc;
 
-use cgmath::Matrix4;
+use image::RgbaImage;
 use wgpu::util::DeviceExt;
+use winit::dpi::PhysicalSize;
 use winit::window::Window;
 
-use crate::world::chunk;
+use crate::error::{Error, Result};
+use crate::world::batch::{ChunkBatch, MULTI_DRAW_FEATURES};
+use crate::world::{chunk, World};
 
-use super::{input::InputHandler, shader::Shader};
+use super::backend::{report_no_adapter, BackendOptions};
+use super::gpu_timer::{GpuTimer, TIMER_FEATURES};
+use super::profiler;
+use super::resources::ResourcePack;
+use super::{input::InputHandler, shader::Shader, ui::Ui};
 
 use super::camera;
 use super::model;
 
+/// Format of the frames of a headless state.
+const HEADLESS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Where frames are presented. A headless state can only render to images.
+enum Output<'a> {
+    Window {
+        surface: wgpu::Surface<'a>,
+        window: Arc<Window>,
+    },
+    Headless,
+}
+
 pub struct State<'a> {
-    surface: wgpu::Surface<'a>,
+    output: Output<'a>,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     pub size: winit::dpi::PhysicalSize<u32>,
-    window: Arc<Window>,
     pub camera: camera::Camera,
+    /// Camera of the tick before the last, drawn frames blend both.
+    pub previous_camera: camera::Camera,
     camera_buffer: wgpu::Buffer,
     pub camera_uniform: camera::CameraUniform,
     camera_bind_group: wgpu::BindGroup,
     pub texture_bind_group_layout: wgpu::BindGroupLayout,
+    /// Layout of the block texture array chunks are drawn with.
+    terrain_bind_group_layout: wgpu::BindGroupLayout,
+    /// Block texture array shared by every chunk.
+    terrain: model::BindTexture,
+    camera_bind_group_layout: wgpu::BindGroupLayout,
+    /// Layout of the storage buffer of chunk records.
+    chunk_bind_group_layout: wgpu::BindGroupLayout,
+    /// Chunk meshes of the last world drawn, packed for the GPU.
+    chunk_batch: ChunkBatch,
+    /// Times the render pass while profiling, None without timestamp queries.
+    gpu_timer: Option<GpuTimer>,
+    /// Depth of the last frame, recreated only when the size or the sample
+    /// count changes. Later passes can only bind it when MSAA is off, with a
+    /// single sample per pixel.
+    pub depth_texture: model::Texture,
+    /// Samples per pixel, frames are resolved into the output when above 1.
+    sample_count: u32,
+    /// Sample counts the adapter can draw with, in increasing order.
+    sample_counts: Vec<u32>,
+    multisampled_view: Option<wgpu::TextureView>,
     shader: Shader,
+    /// Textures and shaders everything is drawn with.
+    pub resources: ResourcePack,
+    pub ui: Ui,
 }
 
 impl<'a> State<'a> {
-    pub async fn new(window: Arc<Window>) -> State<'a> {
+    /// Fails with `Error::NoAdapter`, after logging the adapters there are,
+    /// when none of the selected backends can draw to the window.
+    pub async fn new(
+        window: Arc<Window>,
+        options: BackendOptions,
+        resources: ResourcePack,
+    ) -> Result<State<'a>> {
         let size = window.inner_size();
 
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::VULKAN,
+            backends: options.backends,
             ..Default::default()
         });
 
-        let surface = instance.create_surface(window.clone()).unwrap();
+        let surface = instance.create_surface(window.clone())?;
 
-        let adapter = instance
+        let Some(adapter) = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
                 power_preference: wgpu::PowerPreference::default(),
                 compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
+                force_fallback_adapter: options.force_fallback_adapter,
             })
             .await
-            .unwrap();
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    required_features: wgpu::Features::empty(),
-                    required_limits: wgpu::Limits::default(),
-                    memory_hints: Default::default(),
-                },
-                None,
-            )
-            .await
-            .unwrap();
+        else {
+            report_no_adapter(&options);
+            return Err(Error::NoAdapter);
+        };
+        let info = adapter.get_info();
+        log::info!("Using {} on {:?}", info.name, info.backend);
 
         let surface_caps = surface.get_capabilities(&adapter);
         let surface_format = surface_caps
@@ -78,6 +120,86 @@ impl<'a> State<'a> {
             desired_maximum_frame_latency: 2,
         };
 
+        let output = Output::Window { surface, window };
+        State::from_adapter(&adapter, output, config, options.sample_count, resources).await
+    }
+
+    /// A state without window, rendering only with `render_to_image`.
+    /// Falls back to the software adapter when there is no GPU, and fails
+    /// with `Error::NoAdapter` when there is no adapter at all.
+    pub async fn headless(width: u32, height: u32) -> Result<State<'a>> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+
+        let mut adapter = None;
+        for force_fallback_adapter in [false, true] {
+            adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::default(),
+                    compatible_surface: None,
+                    force_fallback_adapter,
+                })
+                .await;
+            if adapter.is_some() {
+                break;
+            }
+        }
+        let adapter = adapter.ok_or(Error::NoAdapter)?;
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: HEADLESS_FORMAT,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+
+        let resources = ResourcePack::embedded();
+        State::from_adapter(&adapter, Output::Headless, config, 1, resources).await
+    }
+
+    async fn from_adapter(
+        adapter: &wgpu::Adapter,
+        output: Output<'a>,
+        config: wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        resources: ResourcePack,
+    ) -> Result<State<'a>> {
+        let size = PhysicalSize::new(config.width, config.height);
+        let surface_format = config.format;
+
+        // Without it only 1 and 4 samples can be used
+        let format_features =
+            adapter.features() & wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
+        let sample_counts =
+            supported_sample_counts(adapter, surface_format, !format_features.is_empty());
+        // Without them chunks are drawn one call each
+        let multi_draw_features = if adapter.features().contains(MULTI_DRAW_FEATURES) {
+            MULTI_DRAW_FEATURES
+        } else {
+            wgpu::Features::empty()
+        };
+        // Without it only the CPU is profiled
+        let timer_features = adapter.features() & TIMER_FEATURES;
+        let sample_count = closest_sample_count(&sample_counts, sample_count);
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    required_features: format_features | multi_draw_features | timer_features,
+                    required_limits: wgpu::Limits::default(),
+                    memory_hints: Default::default(),
+                },
+                None,
+            )
+            .await?;
+
         let mut camera = camera::Camera {
             scale: 8.0,
             position: [0.0, -1.0],
@@ -85,6 +207,12 @@ impl<'a> State<'a> {
             near: -100.0,
             far: 200.0,
             eye_position: [-1.0, -1.0, -1.0].into(),
+            free_rotation: false,
+            turn: None,
+            orbit_drag: 0.0,
+            perspective: false,
+            fov: camera::FOV,
+            focus: None,
         };
 
         let mut camera_uniform = camera::CameraUniform::new(size.width as f32, size.height as f32);
@@ -100,7 +228,7 @@ impl<'a> State<'a> {
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -121,35 +249,70 @@ impl<'a> State<'a> {
         });
 
         let texture_bind_group_layout = device.create_bind_group_layout(&model::Texture::desc());
+        let terrain_bind_group_layout =
+            device.create_bind_group_layout(&model::Texture::array_desc());
 
         let chunk_bind_group_layout = device.create_bind_group_layout(&chunk::Uniforms::desc());
 
         let shader = Shader::new(
             &device,
             &texture_bind_group_layout,
+            &terrain_bind_group_layout,
             &camera_bind_group_layout,
             &chunk_bind_group_layout,
             surface_format,
-        );
+            sample_count,
+            &resources,
+        )?;
+
+        let ui = Ui::new(&device, &queue, &texture_bind_group_layout, &resources)?;
+        let terrain = model::BindTexture::terrain(
+            &terrain_bind_group_layout,
+            &resources.terrain,
+            &device,
+            &queue,
+        )?;
+
+        let chunk_batch = ChunkBatch::new(&device, &chunk_bind_group_layout);
+        let gpu_timer = GpuTimer::new(&device, &queue);
+
+        let depth_texture =
+            model::Texture::create_depth_texture(&device, &config, sample_count, "Depth texture");
+        let multisampled_view = create_multisampled_view(&device, &config, sample_count);
 
-        Self {
-            surface,
+        Ok(Self {
+            output,
             device,
             queue,
             config,
             size,
-            window,
             camera,
+            previous_camera: camera,
             camera_buffer,
             camera_uniform,
             camera_bind_group,
             texture_bind_group_layout,
+            terrain_bind_group_layout,
+            terrain,
+            camera_bind_group_layout,
+            chunk_bind_group_layout,
+            chunk_batch,
+            gpu_timer,
+            depth_texture,
+            sample_count,
+            sample_counts,
+            multisampled_view,
             shader,
-        }
+            resources,
+            ui,
+        })
     }
 
-    pub fn window(&self) -> &Window {
-        &self.window
+    pub fn window(&self) -> Option<&Window> {
+        match &self.output {
+            Output::Window { window, .. } => Some(window),
+            Output::Headless => None,
+        }
     }
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
@@ -173,48 +336,264 @@ impl<'a> State<'a> {
                 0,
                 bytemuck::cast_slice(&[self.camera_uniform]),
             );
-            self.surface.configure(&self.device, &self.config);
+            if let Output::Window { surface, .. } = &self.output {
+                surface.configure(&self.device, &self.config);
+            }
+            self.create_targets();
+        }
+    }
+
+    fn create_targets(&mut self) {
+        self.depth_texture = model::Texture::create_depth_texture(
+            &self.device,
+            &self.config,
+            self.sample_count,
+            "Depth texture",
+        );
+        self.multisampled_view =
+            create_multisampled_view(&self.device, &self.config, self.sample_count);
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// Draws with `sample_count` samples per pixel, or the closest count
+    /// below it the adapter supports. The pipelines are rebuilt for it.
+    pub fn set_sample_count(&mut self, sample_count: u32) {
+        let sample_count = closest_sample_count(&self.sample_counts, sample_count);
+        if sample_count == self.sample_count {
+            return;
+        }
+        self.shader = match self.build_shader(sample_count, &self.resources) {
+            Ok(shader) => shader,
+            Err(e) => {
+                log::error!("Could not antialias with {} samples: {}", sample_count, e);
+                return;
+            }
+        };
+        self.sample_count = sample_count;
+        self.create_targets();
+        log::info!("Antialiasing with {} samples per pixel", sample_count);
+    }
+
+    fn build_shader(&self, sample_count: u32, resources: &ResourcePack) -> Result<Shader> {
+        Shader::new(
+            &self.device,
+            &self.texture_bind_group_layout,
+            &self.terrain_bind_group_layout,
+            &self.camera_bind_group_layout,
+            &self.chunk_bind_group_layout,
+            self.config.format,
+            sample_count,
+            resources,
+        )
+    }
+
+    /// Rebuilds the pipelines from the shaders in `dir`. When one doesn't
+    /// compile the error is returned and the current pipelines are kept.
+    pub fn reload_shaders(&mut self, dir: &Path) -> Result<()> {
+        let resources = self.resources.with_shaders(dir);
+        self.shader = self.build_shader(self.sample_count, &resources)?;
+        self.resources = resources;
+        Ok(())
+    }
+
+    /// Reads the resource pack again and rebuilds the terrain, the UI and
+    /// the pipelines from it. The shaders of `shader_dir`, the watched
+    /// directory in dev mode, still replace those of the pack.
+    pub fn reload_resources(&mut self, shader_dir: Option<&Path>) -> Result<()> {
+        let mut resources = self.resources.reload();
+        if let Some(dir) = shader_dir {
+            resources = resources.with_shaders(dir);
         }
+        let shader = self.build_shader(self.sample_count, &resources)?;
+        let terrain = model::BindTexture::terrain(
+            &self.terrain_bind_group_layout,
+            &resources.terrain,
+            &self.device,
+            &self.queue,
+        )?;
+        self.ui = Ui::new(
+            &self.device,
+            &self.queue,
+            &self.texture_bind_group_layout,
+            &resources,
+        )?;
+        self.terrain = terrain;
+        self.shader = shader;
+        self.resources = resources;
+        Ok(())
+    }
+
+    /// Switches to the next supported sample count, back to 1 after the last.
+    pub fn cycle_sample_count(&mut self) {
+        let next = self
+            .sample_counts
+            .iter()
+            .copied()
+            .find(|&count| count > self.sample_count)
+            .unwrap_or(1);
+        self.set_sample_count(next);
     }
 
-    pub fn input(&mut self, input: &InputHandler) -> bool {
+    pub fn input(&mut self, input: &InputHandler, captured: bool, dt: f32) -> bool {
         self.camera.controller(
             input,
-            &mut self.camera_uniform,
-            &mut self.queue,
-            &self.camera_buffer,
+            self.camera_uniform.relation,
+            self.size,
+            captured,
+            dt,
         );
 
         false
     }
 
+    /// Uploads the matrix of `camera`, which may sit between two ticks.
+    pub fn write_camera(&mut self, camera: &mut camera::Camera) {
+        self.camera_uniform.update_view_proj(camera);
+        self.queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[self.camera_uniform]),
+        );
+    }
+
     pub fn render(
-        &self,
+        &mut self,
         models: &Vec<model::Model>,
-        world: &crate::world::World,
-        camera_m: Matrix4<f32>,
-        relation: [f32; 2],
-    ) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
+        world: &World,
+    ) -> std::result::Result<(), wgpu::SurfaceError> {
+        self.update_chunks(world);
+        let Output::Window { surface, .. } = &self.output else {
+            return Ok(());
+        };
+        let output = surface.get_current_texture()?;
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
-        let depth_texture = self.device.create_texture(&wgpu::TextureDescriptor {
+        self.draw(&view, models, true);
+        output.present();
+
+        Ok(())
+    }
+
+    /// Draws `world` seen from `camera` into an image the size of the state,
+    /// without models or UI. Fails when the image can't be read back, as
+    /// after losing the device.
+    pub fn render_to_image(
+        &mut self,
+        world: &World,
+        camera: &mut camera::Camera,
+    ) -> Result<RgbaImage> {
+        let PhysicalSize { width, height } = self.size;
+        self.write_camera(camera);
+        self.update_chunks(world);
+
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
             size: wgpu::Extent3d {
-                width: self.config.width,
-                height: self.config.height,
+                width,
+                height,
                 depth_or_array_layers: 1,
             },
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            label: None,
+            format: self.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            label: Some("Image texture"),
             view_formats: &[],
         });
-        let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.draw(&view, &[], false);
+
+        // Rows of a copy must be aligned
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let bytes_per_row = (4 * width).div_ceil(align) * align;
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Image buffer"),
+            size: (bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Image Encoder"),
+            });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        self.queue.submit(iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        // The callback has run once the device is idle, dropping the sender
+        // when it could not
+        match receiver.recv() {
+            Ok(result) => result?,
+            Err(_) => return Err(Error::BufferMap(wgpu::BufferAsyncError)),
+        }
+
+        let mut image = RgbaImage::new(width, height);
+        {
+            let data = slice.get_mapped_range();
+            for (y, row) in data.chunks(bytes_per_row as usize).enumerate() {
+                let start = y * 4 * width as usize;
+                image.as_mut()[start..start + 4 * width as usize]
+                    .copy_from_slice(&row[..4 * width as usize]);
+            }
+        }
+        buffer.unmap();
+
+        if matches!(
+            self.config.format,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        ) {
+            image.pixels_mut().for_each(|pixel| pixel.0.swap(0, 2));
+        }
+        Ok(image)
+    }
+
+    fn update_chunks(&mut self, world: &World) {
+        let _span = profiler::span("upload chunks");
+        self.chunk_batch
+            .update(&self.device, &self.queue, &self.chunk_bind_group_layout, world);
+    }
+
+    /// Draw calls spent on chunks each frame.
+    pub fn chunk_draw_calls(&self) -> usize {
+        self.chunk_batch.draw_calls()
+    }
+
+    /// Records and submits one frame into `view`, with the chunks of the
+    /// last `update_chunks`.
+    fn draw(&mut self, view: &wgpu::TextureView, models: &[model::Model], ui: bool) {
+        let _span = profiler::span("draw");
+        let timed = match &mut self.gpu_timer {
+            Some(timer) => timer.begin_frame(&self.device),
+            None => false,
+        };
+
+        // Multisampled frames are resolved into `view` at the end of the pass
+        let (target, resolve_target) = match &self.multisampled_view {
+            Some(multisampled_view) => (multisampled_view, Some(view)),
+            None => (view, None),
+        };
 
         let mut encoder = self
             .device
@@ -226,8 +605,8 @@ impl<'a> State<'a> {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
+                    view: target,
+                    resolve_target,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color {
                             r: 0.1,
@@ -239,7 +618,7 @@ impl<'a> State<'a> {
                     },
                 })],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &depth_view,
+                    view: &self.depth_texture.view,
                     depth_ops: Some(wgpu::Operations {
                         load: wgpu::LoadOp::Clear(1.0),
                         store: wgpu::StoreOp::Store,
@@ -247,7 +626,11 @@ impl<'a> State<'a> {
                     stencil_ops: None,
                 }),
                 occlusion_query_set: None,
-                timestamp_writes: None,
+                timestamp_writes: self
+                    .gpu_timer
+                    .as_ref()
+                    .filter(|_| timed)
+                    .map(|timer| timer.pass_writes()),
             });
 
             models.iter().for_each(|x| {
@@ -258,12 +641,92 @@ impl<'a> State<'a> {
                 )
             });
 
-            world.render(&mut render_pass, &self.shader.render_pipeline_chunk, &self.camera_bind_group, camera_m, relation);
+            self.chunk_batch.render(
+                &mut render_pass,
+                &self.shader.render_pipeline_chunk,
+                &self.terrain.diffuse_bind_group,
+                &self.camera_bind_group,
+            );
+
+            if ui {
+                self.ui.render(&mut render_pass, &self.shader.render_pipeline_ui);
+            }
         }
 
-        self.queue.submit(iter::once(encoder.finish()));
-        output.present();
+        if let Some(timer) = self.gpu_timer.as_mut().filter(|_| timed) {
+            timer.resolve(&mut encoder);
+            self.queue.submit(iter::once(encoder.finish()));
+            timer.end_frame();
+        } else {
+            self.queue.submit(iter::once(encoder.finish()));
+        }
+    }
+}
 
-        Ok(())
+/// Sample counts from 1 to 8 that both the output and depth formats
+/// support. Only 1 and 4 are guaranteed without `adapter_specific` features.
+fn supported_sample_counts(
+    adapter: &wgpu::Adapter,
+    format: wgpu::TextureFormat,
+    adapter_specific: bool,
+) -> Vec<u32> {
+    [1, 2, 4, 8]
+        .into_iter()
+        .filter(|&count| {
+            if !adapter_specific {
+                return count == 1 || count == 4;
+            }
+            [format, model::Texture::DEPTH_FORMAT].iter().all(|&format| {
+                adapter
+                    .get_texture_format_features(format)
+                    .flags
+                    .sample_count_supported(count)
+            })
+        })
+        .collect()
+}
+
+/// Largest of `sample_counts` not above `sample_count`.
+fn closest_sample_count(sample_counts: &[u32], sample_count: u32) -> u32 {
+    let closest = sample_counts
+        .iter()
+        .copied()
+        .filter(|&count| count <= sample_count)
+        .max()
+        .unwrap_or(1);
+    if closest != sample_count {
+        log::warn!(
+            "{} samples per pixel are not supported, using {}",
+            sample_count,
+            closest
+        );
+    }
+    closest
+}
+
+/// Color target frames are drawn into before being resolved, None when
+/// there is a single sample per pixel.
+fn create_multisampled_view(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    sample_count: u32,
+) -> Option<wgpu::TextureView> {
+    if sample_count == 1 {
+        return None;
     }
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Multisampled texture"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
 }
@@ -0,0 +1,352 @@
+use std::ops::Range;
+
+use winit::dpi::PhysicalSize;
+
+use crate::error::Result;
+use crate::world::block::{ATLAS_ROWS, BLOCKS};
+
+use super::hotbar::{Hotbar, Rect};
+use super::model::BindTexture;
+use super::resources::ResourcePack;
+
+const MAX_QUADS: usize = 4096;
+/// Glyphs in the font texture, laid out as 16 columns of 8 rows.
+const FONT_COLUMNS: u32 = 16;
+const FONT_ROWS: u32 = 8;
+const GLYPH_SIZE: u32 = 8;
+const ICON_INSET: f32 = 8.0;
+const SELECTED_GROW: f32 = 4.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Sprite {
+    Atlas,
+    Frame,
+    Arrow,
+    Font,
+}
+
+/// Screen space quads drawn over the world.
+pub struct Ui {
+    atlas: BindTexture,
+    frame: BindTexture,
+    arrow: BindTexture,
+    font: BindTexture,
+    vertex_buffer: wgpu::Buffer,
+    vertex: Vec<UiVertex>,
+    batches: Vec<(Sprite, Range<u32>)>,
+}
+
+impl Ui {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        resources: &ResourcePack,
+    ) -> Result<Self> {
+        let atlas = BindTexture::new(
+            texture_bind_group_layout,
+            &resources.terrain,
+            device,
+            queue,
+            "UI atlas",
+        )?;
+        let frame = BindTexture::new(
+            texture_bind_group_layout,
+            &resources.frame,
+            device,
+            queue,
+            "UI frame",
+        )?;
+        let arrow = BindTexture::new(
+            texture_bind_group_layout,
+            &resources.arrow,
+            device,
+            queue,
+            "UI arrow",
+        )?;
+        let font = BindTexture::from_image(
+            texture_bind_group_layout,
+            &font_image(),
+            device,
+            queue,
+            "UI font",
+        );
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("UI Vertex Buffer"),
+            size: (MAX_QUADS * 6 * std::mem::size_of::<UiVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Ok(Self {
+            atlas,
+            frame,
+            arrow,
+            font,
+            vertex_buffer,
+            vertex: vec![],
+            batches: vec![],
+        })
+    }
+
+    /// Starts laying out a new frame.
+    pub fn clear(&mut self) {
+        self.vertex.clear();
+        self.batches.clear();
+    }
+
+    /// Sends everything laid out since `clear` to the GPU.
+    pub fn upload(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertex));
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertex.len()
+    }
+
+    /// Lines of ASCII text over a dark background, from the top left corner
+    /// `position` in pixels. Each glyph is `GLYPH_SIZE * scale` pixels wide.
+    pub fn text(
+        &mut self,
+        lines: &[String],
+        position: [f32; 2],
+        scale: f32,
+        size: PhysicalSize<u32>,
+    ) {
+        let glyph = GLYPH_SIZE as f32 * scale;
+        let line_height = glyph + scale * 2.0;
+        let columns = lines.iter().map(|l| l.len()).max().unwrap_or(0);
+        if columns == 0 {
+            return;
+        }
+        let [x, y] = position;
+        let background = [
+            x - scale * 2.0,
+            y - scale * 2.0,
+            columns as f32 * glyph + scale * 4.0,
+            lines.len() as f32 * line_height + scale * 2.0,
+        ];
+        self.quad(
+            Sprite::Frame,
+            background,
+            [0.25, 0.25, 0.75, 0.75],
+            [0.0, 0.0, 0.0, 0.5],
+            size,
+        );
+
+        for (row, line) in lines.iter().enumerate() {
+            for (column, c) in line.bytes().enumerate() {
+                if c == b' ' {
+                    continue;
+                }
+                let c = if c.is_ascii() { c as u32 } else { b'?' as u32 };
+                let (u, v) = (c % FONT_COLUMNS, c / FONT_COLUMNS);
+                let uv = [
+                    u as f32 / FONT_COLUMNS as f32,
+                    v as f32 / FONT_ROWS as f32,
+                    (u + 1) as f32 / FONT_COLUMNS as f32,
+                    (v + 1) as f32 / FONT_ROWS as f32,
+                ];
+                let rect = [
+                    x + column as f32 * glyph,
+                    y + row as f32 * line_height,
+                    glyph,
+                    glyph,
+                ];
+                self.quad(Sprite::Font, rect, uv, [1.0; 4], size);
+            }
+        }
+    }
+
+    /// Lays out the hotbar and, when open, the palette.
+    pub fn hotbar(&mut self, hotbar: &Hotbar, size: PhysicalSize<u32>) {
+        for (slot, &block) in hotbar.slots.iter().enumerate() {
+            let rect = hotbar.slot_rect(slot, size);
+            if slot == hotbar.selected {
+                self.quad(
+                    Sprite::Frame,
+                    grow(rect, SELECTED_GROW),
+                    FULL_UV,
+                    [1.0, 1.0, 1.0, 0.9],
+                    size,
+                );
+            } else {
+                self.quad(Sprite::Frame, rect, FULL_UV, [0.2, 0.2, 0.2, 0.6], size);
+            }
+            self.quad(
+                Sprite::Atlas,
+                grow(rect, -ICON_INSET),
+                icon_uv(block),
+                [1.0; 4],
+                size,
+            );
+        }
+
+        if hotbar.palette_open {
+            self.quad(
+                Sprite::Frame,
+                hotbar.palette_panel(size),
+                FULL_UV,
+                [0.0, 0.0, 0.0, 0.6],
+                size,
+            );
+            for (index, block) in BLOCKS.iter().enumerate() {
+                let rect = hotbar.palette_rect(index, size);
+                let color = if block.id == hotbar.block() {
+                    [1.0, 1.0, 1.0, 0.9]
+                } else {
+                    [0.2, 0.2, 0.2, 0.6]
+                };
+                self.quad(Sprite::Frame, rect, FULL_UV, color, size);
+                self.quad(
+                    Sprite::Atlas,
+                    grow(rect, -ICON_INSET),
+                    icon_uv(block.id),
+                    [1.0; 4],
+                    size,
+                );
+            }
+            if let Some(index) = hotbar.hovered {
+                let [x, y, w, h] = hotbar.palette_rect(index, size);
+                let arrow = [x - w / 2.0, y + h / 4.0, w / 2.0, h / 2.0];
+                self.quad(Sprite::Arrow, arrow, FULL_UV, [1.0; 4], size);
+            }
+        }
+    }
+
+    fn quad(
+        &mut self,
+        sprite: Sprite,
+        rect: Rect,
+        uv: [f32; 4],
+        color: [f32; 4],
+        size: PhysicalSize<u32>,
+    ) {
+        if self.vertex.len() + 6 > MAX_QUADS * 6 {
+            return;
+        }
+        let [x, y, w, h] = rect;
+        let [u0, v0, u1, v1] = uv;
+        let to_ndc = |px: f32, py: f32| {
+            [
+                px / size.width as f32 * 2.0 - 1.0,
+                1.0 - py / size.height as f32 * 2.0,
+            ]
+        };
+        let corners = [
+            UiVertex::new(to_ndc(x, y), [u0, v0], color),
+            UiVertex::new(to_ndc(x, y + h), [u0, v1], color),
+            UiVertex::new(to_ndc(x + w, y + h), [u1, v1], color),
+            UiVertex::new(to_ndc(x + w, y), [u1, v0], color),
+        ];
+
+        let start = self.vertex.len() as u32;
+        for i in [0, 1, 2, 0, 2, 3] {
+            self.vertex.push(corners[i]);
+        }
+        let end = self.vertex.len() as u32;
+        match self.batches.last_mut() {
+            Some((last, range)) if *last == sprite => range.end = end,
+            _ => self.batches.push((sprite, start..end)),
+        }
+    }
+
+    pub fn render(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        render_pipeline: &wgpu::RenderPipeline,
+    ) {
+        if self.vertex.is_empty() {
+            return;
+        }
+        render_pass.set_pipeline(render_pipeline);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        for (sprite, range) in &self.batches {
+            let texture = match sprite {
+                Sprite::Atlas => &self.atlas,
+                Sprite::Frame => &self.frame,
+                Sprite::Arrow => &self.arrow,
+                Sprite::Font => &self.font,
+            };
+            render_pass.set_bind_group(0, &texture.diffuse_bind_group, &[]);
+            render_pass.draw(range.clone(), 0..1);
+        }
+    }
+}
+
+const FULL_UV: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+
+/// Side face of a block in the terrain atlas.
+fn icon_uv(voxel_id: u8) -> [f32; 4] {
+    let rows = ATLAS_ROWS as f32;
+    [
+        1.0 / 3.0,
+        voxel_id as f32 / rows,
+        2.0 / 3.0,
+        (voxel_id as f32 + 1.0) / rows,
+    ]
+}
+
+/// White glyphs of the basic 8x8 font on a transparent background.
+fn font_image() -> image::RgbaImage {
+    let mut image = image::RgbaImage::new(FONT_COLUMNS * GLYPH_SIZE, FONT_ROWS * GLYPH_SIZE);
+    for (c, glyph) in font8x8::legacy::BASIC_LEGACY.iter().enumerate() {
+        let (gx, gy) = (c as u32 % FONT_COLUMNS, c as u32 / FONT_COLUMNS);
+        for (y, row) in glyph.iter().enumerate() {
+            for x in 0..GLYPH_SIZE {
+                if row >> x & 1 == 1 {
+                    let pixel = image::Rgba([255, 255, 255, 255]);
+                    image.put_pixel(gx * GLYPH_SIZE + x, gy * GLYPH_SIZE + y as u32, pixel);
+                }
+            }
+        }
+    }
+    image
+}
+
+fn grow(rect: Rect, amount: f32) -> Rect {
+    let [x, y, w, h] = rect;
+    [x - amount, y - amount, w + 2.0 * amount, h + 2.0 * amount]
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct UiVertex {
+    position: [f32; 2],
+    uv_coord: [f32; 2],
+    color: [f32; 4],
+}
+
+impl UiVertex {
+    pub fn new(position: [f32; 2], uv_coord: [f32; 2], color: [f32; 4]) -> Self {
+        Self {
+            position,
+            uv_coord,
+            color,
+        }
+    }
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<UiVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
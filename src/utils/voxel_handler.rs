@@ -9,7 +9,8 @@ use crate::world::{
 ////////////////////////////////////////////////////////////////////
 
 use super::camera;
-use cgmath::{InnerSpace, Vector3, Vector4};
+use super::profiler;
+use cgmath::{InnerSpace, SquareMatrix, Vector3, Vector4};
 use winit::dpi::{PhysicalPosition, PhysicalSize};
 
 #[derive(Clone, Copy)]
@@ -46,6 +47,11 @@ impl VoxelHandler {
         relation: [f32; 2],
         world: &World,
     ) {
+        let _span = profiler::span("voxel_handler");
+        if camera.perspective {
+            self.update_perspective(camera, mouse_pos, size, relation, world);
+            return;
+        }
         //Constantes en la ejecucion de la funcion
         let c_matrix = camera.build_view_projection_matrix();
         let mut linex: cgmath::Vector4<f32> = cgmath::Vector4::unit_x() / CHUNK_SIZE as f32;
@@ -123,7 +129,7 @@ impl VoxelHandler {
                 || pz >= WORLD_D as f32 * CHUNK_SIZE as f32)
             {
                 let (voxel_index, chunk_index) = get_voxel(px, py, pz);
-                if world.voxels[chunk_index][voxel_index] != 0 {
+                if world.voxels[chunk_index][voxel_index] != 0 && !world.is_cut(py as i32) {
                     [x, y, z] = [px, py, pz];
                     break;
                 }
@@ -135,6 +141,45 @@ impl VoxelHandler {
             ay -= 0.05;
         }
 
+        self.set_hit(x, y, z);
+    }
+
+    /// Picking for the perspective camera: casts the ray under the cursor
+    /// through the voxel grid.
+    fn update_perspective(
+        &mut self,
+        camera: &mut camera::Camera,
+        mouse_pos: PhysicalPosition<f32>,
+        size: PhysicalSize<u32>,
+        relation: [f32; 2],
+        world: &World,
+    ) {
+        let Some(inverse) = camera.build_view_projection_matrix().invert() else {
+            return;
+        };
+        let ndc = [
+            (mouse_pos.x / size.width as f32 * 2.0 - 1.0) / relation[0],
+            (1.0 - mouse_pos.y / size.height as f32 * 2.0) / relation[1],
+        ];
+        // Render space is in chunks and centred, the grid is in voxels
+        let offset = Vector3::new(WORLD_W as f32 / 2.0, WORLD_H as f32, WORLD_D as f32 / 2.0);
+        let unproject = |depth: f32| {
+            let point = inverse * Vector4::new(ndc[0], ndc[1], depth, 1.0);
+            (point.truncate() / point.w + offset) * CHUNK_SIZE as f32
+        };
+        let near = unproject(0.0);
+        let far = unproject(1.0);
+
+        let Some((voxel, normal)) = raycast(world, near, far - near) else {
+            return;
+        };
+        // A point just inside the hit face, as the isometric picking gives
+        let [x, y, z] = [0, 1, 2].map(|i| voxel[i] as f32 + 0.5 + normal[i] as f32 * 0.49);
+        self.set_hit(x, y, z);
+    }
+
+    /// Stores the voxel at `x, y, z` and the face closest to that point.
+    fn set_hit(&mut self, x: f32, y: f32, z: f32) {
         let direction = self.get_direction(x, y, z);
 
         /* let [px, py, pz] = [
@@ -269,6 +314,83 @@ impl VoxelHandler {
     }
 }
 
+/// First solid voxel along `direction` from `origin`, in voxels, and the
+/// normal of the face the ray enters it through.
+fn raycast(
+    world: &World,
+    origin: Vector3<f32>,
+    direction: Vector3<f32>,
+) -> Option<([i32; 3], [i32; 3])> {
+    let size = [
+        (WORLD_W * CHUNK_SIZE as u32) as f32,
+        (WORLD_H * CHUNK_SIZE as u32) as f32,
+        (WORLD_D * CHUNK_SIZE as u32) as f32,
+    ];
+    let origin: [f32; 3] = origin.into();
+    let direction: [f32; 3] = direction.normalize().into();
+
+    // Start where the ray enters the world box
+    let (mut t_min, mut t_max) = (0.0f32, f32::INFINITY);
+    for i in 0..3 {
+        if direction[i] == 0.0 {
+            if origin[i] < 0.0 || origin[i] >= size[i] {
+                return None;
+            }
+            continue;
+        }
+        let a = (0.0 - origin[i]) / direction[i];
+        let b = (size[i] - origin[i]) / direction[i];
+        t_min = t_min.max(a.min(b));
+        t_max = t_max.min(a.max(b));
+    }
+    if t_min > t_max {
+        return None;
+    }
+
+    let start = [0, 1, 2].map(|i| origin[i] + direction[i] * (t_min + 0.001));
+    let mut voxel = start.map(|v| v.floor() as i32);
+    let step = direction.map(|d| if d > 0.0 { 1 } else { -1 });
+    let delta = direction.map(|d| (1.0 / d).abs());
+    let mut next = [0, 1, 2].map(|i| {
+        let boundary = voxel[i] as f32 + if step[i] > 0 { 1.0 } else { 0.0 };
+        if direction[i] == 0.0 {
+            f32::INFINITY
+        } else {
+            (boundary - start[i]) / direction[i]
+        }
+    });
+    let mut normal = [0; 3];
+    // The entry face of the world box, for a hit on its first voxel
+    if let Some(axis) = (0..3).find(|&i| {
+        direction[i] != 0.0
+            && ((0.0 - origin[i]) / direction[i]).min((size[i] - origin[i]) / direction[i]) == t_min
+    }) {
+        normal[axis] = -step[axis];
+    }
+
+    let max_steps = (size[0] + size[1] + size[2]) as usize;
+    for _ in 0..max_steps {
+        if world.get_voxel(voxel) != 0 && !world.is_cut(voxel[1]) {
+            return Some((voxel, normal));
+        }
+        let axis = if next[0] < next[1] && next[0] < next[2] {
+            0
+        } else if next[1] < next[2] {
+            1
+        } else {
+            2
+        };
+        voxel[axis] += step[axis];
+        next[axis] += delta[axis];
+        normal = [0; 3];
+        normal[axis] = -step[axis];
+        if voxel[axis] < 0 || voxel[axis] as f32 >= size[axis] {
+            return None;
+        }
+    }
+    None
+}
+
 fn get_voxel(x: f32, y: f32, z: f32) -> (usize, usize) {
     let [cx, cy, cz] = [
         x as u32 / CHUNK_SIZE as u32,
@@ -293,3 +415,90 @@ enum Direction {
     Y(f32),
     X(f32),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::WORLD_VOL;
+
+    /// Empty world without meshes, only `get_voxel` works on it.
+    fn empty_world(solid: &[[i32; 3]]) -> World {
+        let mut world = World::new();
+        world.voxels = vec![vec![0; CHUNK_AREA * CHUNK_SIZE as usize]; WORLD_VOL as usize];
+        for &position in solid {
+            let (chunk_index, voxel_index) = World::index(position).unwrap();
+            world.voxels[chunk_index][voxel_index] = 1;
+        }
+        world
+    }
+
+    #[test]
+    fn axis_aligned_rays_hit_the_facing_side() {
+        let world = empty_world(&[[5, 10, 10]]);
+        let hit = raycast(&world, Vector3::new(0.5, 10.5, 10.5), Vector3::unit_x());
+        assert_eq!(hit, Some(([5, 10, 10], [-1, 0, 0])));
+        let hit = raycast(&world, Vector3::new(9.5, 10.5, 10.5), -Vector3::unit_x());
+        assert_eq!(hit, Some(([5, 10, 10], [1, 0, 0])));
+        let hit = raycast(&world, Vector3::new(5.5, 0.5, 10.5), Vector3::unit_y());
+        assert_eq!(hit, Some(([5, 10, 10], [0, -1, 0])));
+        let hit = raycast(&world, Vector3::new(5.5, 10.5, 30.5), -Vector3::unit_z());
+        assert_eq!(hit, Some(([5, 10, 10], [0, 0, 1])));
+    }
+
+    #[test]
+    fn rays_from_outside_enter_through_the_world_box() {
+        let top = (WORLD_H * CHUNK_SIZE as u32) as i32 - 1;
+        let world = empty_world(&[[3, top, 3]]);
+        let hit = raycast(&world, Vector3::new(3.5, 100.0, 3.5), -Vector3::unit_y());
+        assert_eq!(hit, Some(([3, top, 3], [0, 1, 0])));
+        let hit = raycast(
+            &world,
+            Vector3::new(-20.0, top as f32 + 0.5, 3.5),
+            Vector3::unit_x(),
+        );
+        assert_eq!(hit, Some(([3, top, 3], [-1, 0, 0])));
+    }
+
+    #[test]
+    fn origins_on_voxel_boundaries() {
+        let world = empty_world(&[[5, 10, 10]]);
+        let hit = raycast(&world, Vector3::new(2.0, 10.5, 10.5), Vector3::unit_x());
+        assert_eq!(hit, Some(([5, 10, 10], [-1, 0, 0])));
+        let hit = raycast(&world, Vector3::new(8.0, 10.5, 10.5), -Vector3::unit_x());
+        assert_eq!(hit, Some(([5, 10, 10], [1, 0, 0])));
+        // On the edge between four columns, the one on the positive side
+        let hit = raycast(&world, Vector3::new(5.0, 20.0, 10.0), -Vector3::unit_y());
+        assert_eq!(hit, Some(([5, 10, 10], [0, 1, 0])));
+    }
+
+    #[test]
+    fn cut_voxels_are_skipped() {
+        let mut world = empty_world(&[[3, 10, 3], [3, 30, 3]]);
+        let origin = Vector3::new(3.5, 60.5, 3.5);
+        assert_eq!(
+            raycast(&world, origin, -Vector3::unit_y()),
+            Some(([3, 30, 3], [0, 1, 0]))
+        );
+        world.cut = Some(20);
+        assert_eq!(
+            raycast(&world, origin, -Vector3::unit_y()),
+            Some(([3, 10, 3], [0, 1, 0]))
+        );
+        world.cut = Some(5);
+        assert_eq!(raycast(&world, origin, -Vector3::unit_y()), None);
+    }
+
+    #[test]
+    fn rays_missing_everything() {
+        let world = empty_world(&[[5, 10, 10]]);
+        assert_eq!(
+            raycast(&world, Vector3::new(0.5, 10.5, 10.5), -Vector3::unit_x()),
+            None
+        );
+        // Parallel to the world but outside it
+        assert_eq!(
+            raycast(&world, Vector3::new(0.5, 100.0, 10.5), Vector3::unit_x()),
+            None
+        );
+    }
+}
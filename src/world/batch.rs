@@ -0,0 +1,284 @@
+use std::mem::size_of;
+
+use wgpu::util::DrawIndirectArgs;
+
+use super::chunk::{ChunkMesh, ChunkVertexPacked, Uniforms};
+use super::World;
+
+/// Features the batch needs to draw every chunk with one indirect call.
+pub const MULTI_DRAW_FEATURES: wgpu::Features =
+    wgpu::Features::MULTI_DRAW_INDIRECT.union(wgpu::Features::INDIRECT_FIRST_INSTANCE);
+/// Fewest vertices a slot holds, so small meshes like the ghost can grow a
+/// little without a repack.
+const MIN_SLOT: u32 = 6 * 256;
+
+/// Range of the vertex buffer a mesh owns, with room to grow.
+struct Slot {
+    /// Version of the mesh last written to the slot.
+    version: u64,
+    first_vertex: u32,
+    capacity: u32,
+}
+
+impl Slot {
+    /// Half again as many vertices as `vertex_count`, in whole faces so every
+    /// slot starts on a face and the shader can still take the corner of a
+    /// face from the vertex index.
+    fn capacity(vertex_count: u32) -> u32 {
+        (vertex_count + vertex_count / 2)
+            .max(MIN_SLOT)
+            .next_multiple_of(6)
+    }
+}
+
+/// The chunk meshes of a world packed into one vertex buffer, drawn with a
+/// single pipeline and bind group. Each mesh owns a slot of the buffer and is
+/// a draw whose instance picks its record in a storage buffer of `Uniforms`.
+/// With multi-draw-indirect the draws are one call, otherwise they are
+/// issued one by one from the same buffers.
+pub struct ChunkBatch {
+    multi_draw: bool,
+    vertex_buffer: wgpu::Buffer,
+    uniform_buffer: wgpu::Buffer,
+    indirect_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    /// One per mesh of the world, in `World::meshes` order.
+    slots: Vec<Slot>,
+    uniforms: Vec<Uniforms>,
+    draws: Vec<DrawIndirectArgs>,
+}
+
+impl ChunkBatch {
+    pub fn new(device: &wgpu::Device, chunk_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        let multi_draw = device.features().contains(MULTI_DRAW_FEATURES);
+        if multi_draw {
+            log::info!("Drawing chunks with multi-draw-indirect");
+        } else {
+            log::info!("Multi-draw-indirect unavailable, drawing chunks one by one");
+        }
+        let uniform_buffer = create_buffer(
+            device,
+            "Chunk uniforms",
+            size_of::<Uniforms>() as u64,
+            wgpu::BufferUsages::STORAGE,
+        );
+        Self {
+            multi_draw,
+            vertex_buffer: create_buffer(
+                device,
+                "Chunk vertices",
+                size_of::<ChunkVertexPacked>() as u64,
+                wgpu::BufferUsages::VERTEX,
+            ),
+            bind_group: create_bind_group(device, chunk_bind_group_layout, &uniform_buffer),
+            uniform_buffer,
+            indirect_buffer: create_buffer(
+                device,
+                "Chunk draws",
+                size_of::<DrawIndirectArgs>() as u64,
+                wgpu::BufferUsages::INDIRECT,
+            ),
+            slots: vec![],
+            uniforms: vec![],
+            draws: vec![],
+        }
+    }
+
+    /// Uploads what changed in `world` since the last call. A rebuilt mesh
+    /// is written into its slot, and every mesh is repacked only when one
+    /// outgrew its slot or meshes came and went. Records are rewritten when
+    /// one moved.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        chunk_bind_group_layout: &wgpu::BindGroupLayout,
+        world: &World,
+    ) {
+        let meshes: Vec<&ChunkMesh> = world.meshes().collect();
+
+        let fits = meshes.len() == self.slots.len()
+            && meshes
+                .iter()
+                .zip(&self.slots)
+                .all(|(mesh, slot)| mesh.vertex_count() <= slot.capacity);
+        if fits {
+            let mut changed = false;
+            for (mesh, slot) in meshes.iter().zip(&mut self.slots) {
+                if mesh.version() != slot.version {
+                    write_vertices(queue, &self.vertex_buffer, slot, mesh);
+                    changed = true;
+                }
+            }
+            if changed {
+                self.write_draws(device, queue, &meshes);
+            }
+        } else {
+            self.pack(device, queue, &meshes);
+        }
+
+        let uniforms: Vec<Uniforms> = meshes.iter().map(|m| m.uniforms()).collect();
+        let bytes: &[u8] = bytemuck::cast_slice(&uniforms);
+        if bytes != bytemuck::cast_slice::<_, u8>(&self.uniforms) {
+            if bytes.len() as u64 > self.uniform_buffer.size() {
+                self.uniform_buffer = create_buffer(
+                    device,
+                    "Chunk uniforms",
+                    bytes.len() as u64,
+                    wgpu::BufferUsages::STORAGE,
+                );
+                self.bind_group =
+                    create_bind_group(device, chunk_bind_group_layout, &self.uniform_buffer);
+            }
+            queue.write_buffer(&self.uniform_buffer, 0, bytes);
+            self.uniforms = uniforms;
+        }
+    }
+
+    /// Gives every mesh a new slot and writes them all.
+    fn pack(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, meshes: &[&ChunkMesh]) {
+        self.slots.clear();
+        let mut first_vertex = 0;
+        for mesh in meshes {
+            let capacity = Slot::capacity(mesh.vertex_count());
+            self.slots.push(Slot {
+                version: mesh.version(),
+                first_vertex,
+                capacity,
+            });
+            first_vertex += capacity;
+        }
+
+        let vertex_size = size_of::<ChunkVertexPacked>() as u64;
+        if first_vertex as u64 * vertex_size > self.vertex_buffer.size() {
+            self.vertex_buffer = create_buffer(
+                device,
+                "Chunk vertices",
+                first_vertex as u64 * vertex_size,
+                wgpu::BufferUsages::VERTEX,
+            );
+        }
+        for (mesh, slot) in meshes.iter().zip(&self.slots) {
+            write_vertices(queue, &self.vertex_buffer, slot, mesh);
+        }
+        self.write_draws(device, queue, meshes);
+    }
+
+    /// One draw per mesh with vertices, from the start of its slot.
+    fn write_draws(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, meshes: &[&ChunkMesh]) {
+        self.draws.clear();
+        for (index, (mesh, slot)) in meshes.iter().zip(&mut self.slots).enumerate() {
+            slot.version = mesh.version();
+            if mesh.vertex_count() > 0 {
+                self.draws.push(DrawIndirectArgs {
+                    vertex_count: mesh.vertex_count(),
+                    instance_count: 1,
+                    first_vertex: slot.first_vertex,
+                    first_instance: index as u32,
+                });
+            }
+        }
+
+        if self.multi_draw && !self.draws.is_empty() {
+            let draws: Vec<u8> = self
+                .draws
+                .iter()
+                .flat_map(|d| d.as_bytes())
+                .copied()
+                .collect();
+            if draws.len() as u64 > self.indirect_buffer.size() {
+                self.indirect_buffer = create_buffer(
+                    device,
+                    "Chunk draws",
+                    draws.len() as u64,
+                    wgpu::BufferUsages::INDIRECT,
+                );
+            }
+            queue.write_buffer(&self.indirect_buffer, 0, &draws);
+        }
+    }
+
+    /// Draws every packed mesh, chunks first and translucent ghosts last.
+    pub fn render(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        render_pipeline: &wgpu::RenderPipeline,
+        terrain_bind_group: &wgpu::BindGroup,
+        camera_bind_group: &wgpu::BindGroup,
+    ) {
+        if self.draws.is_empty() {
+            return;
+        }
+        render_pass.set_pipeline(render_pipeline);
+        render_pass.set_bind_group(0, terrain_bind_group, &[]);
+        render_pass.set_bind_group(1, camera_bind_group, &[]);
+        render_pass.set_bind_group(2, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        if self.multi_draw {
+            render_pass.multi_draw_indirect(&self.indirect_buffer, 0, self.draws.len() as u32);
+        } else {
+            for draw in &self.draws {
+                render_pass.draw(
+                    draw.first_vertex..draw.first_vertex + draw.vertex_count,
+                    draw.first_instance..draw.first_instance + 1,
+                );
+            }
+        }
+    }
+
+    /// Draw calls `render` issues.
+    pub fn draw_calls(&self) -> usize {
+        if self.multi_draw {
+            self.draws.len().min(1)
+        } else {
+            self.draws.len()
+        }
+    }
+}
+
+fn write_vertices(
+    queue: &wgpu::Queue,
+    vertex_buffer: &wgpu::Buffer,
+    slot: &Slot,
+    mesh: &ChunkMesh,
+) {
+    if mesh.vertex_count() == 0 {
+        return;
+    }
+    let vertex_size = size_of::<ChunkVertexPacked>() as u64;
+    queue.write_buffer(
+        vertex_buffer,
+        slot.first_vertex as u64 * vertex_size,
+        bytemuck::cast_slice(mesh.vertices()),
+    );
+}
+
+/// Buffers grow to the next power of two so repacking rarely reallocates.
+fn create_buffer(
+    device: &wgpu::Device,
+    label: &str,
+    size: u64,
+    usage: wgpu::BufferUsages,
+) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: size.next_power_of_two(),
+        usage: usage | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    chunk_bind_group_layout: &wgpu::BindGroupLayout,
+    uniform_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Chunk uniforms bind group"),
+        layout: chunk_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: uniform_buffer.as_entire_binding(),
+        }],
+    })
+}
@@ -0,0 +1,54 @@
+use image::RgbaImage;
+
+/// Rows in `tex_array_0.png`, one per voxel id. Each row holds the bottom,
+/// side and top faces of a block from left to right.
+pub const ATLAS_ROWS: u32 = 8;
+pub const ATLAS_COLUMNS: u32 = 3;
+/// Layers of the terrain texture array per voxel id, for the top, side and
+/// bottom faces in that order.
+pub const FACE_LAYERS: u32 = 3;
+
+/// Column and row of a tile in `tex_array_0.png`.
+pub type Tile = [u32; 2];
+
+#[derive(Clone, Copy, Debug)]
+pub struct Block {
+    pub id: u8,
+    pub name: &'static str,
+    /// Tiles of the top, side and bottom faces.
+    pub faces: [Tile; 3],
+}
+
+/// Faces laid out like the atlas rows, whose index is the voxel id.
+const fn atlas_row(row: u32) -> [Tile; 3] {
+    [[2, row], [1, row], [0, row]]
+}
+
+/// Every block that can be placed. Id 0 is air and has no entry.
+pub const BLOCKS: [Block; 7] = [
+    Block { id: 1, name: "sand", faces: atlas_row(1) },
+    Block { id: 2, name: "grass", faces: atlas_row(2) },
+    Block { id: 3, name: "dirt", faces: atlas_row(3) },
+    Block { id: 4, name: "stone", faces: atlas_row(4) },
+    Block { id: 5, name: "snow", faces: atlas_row(5) },
+    Block { id: 6, name: "pink", faces: atlas_row(6) },
+    Block { id: 7, name: "log", faces: atlas_row(7) },
+];
+
+/// Slices the atlas into the layers of the terrain texture array. Block
+/// `id` face `f` ends up in layer `id * FACE_LAYERS + f`, layers of unused
+/// ids are left transparent.
+pub fn terrain_layers(atlas: &RgbaImage) -> Vec<RgbaImage> {
+    let (width, height) = (atlas.width() / ATLAS_COLUMNS, atlas.height() / ATLAS_ROWS);
+    let ids = BLOCKS.iter().map(|b| b.id as u32 + 1).max().unwrap_or(1);
+    let mut layers = vec![RgbaImage::new(width, height); (ids * FACE_LAYERS) as usize];
+    for block in BLOCKS {
+        for (face, [column, row]) in block.faces.into_iter().enumerate() {
+            let layer = block.id as usize * FACE_LAYERS as usize + face;
+            layers[layer] =
+                image::imageops::crop_imm(atlas, column * width, row * height, width, height)
+                    .to_image();
+        }
+    }
+    layers
+}
@@ -1,9 +1,10 @@
-use noise::{NoiseFn, Simplex};
-use wgpu::util::DeviceExt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
 
-use crate::utils::model::BindTexture;
+use noise::{NoiseFn, Simplex};
 
 use super::{WORLD_D, WORLD_H, WORLD_W};
+use crate::utils::profiler;
 
 pub const CHUNK_SIZE: u8 = 32;
 #[allow(dead_code)]
@@ -12,6 +13,10 @@ pub const CHUNK_AREA: usize = CHUNK_SIZE as usize * CHUNK_SIZE as usize;
 const CHUNK_VOL: usize = CHUNK_AREA * CHUNK_SIZE as usize;
 const PERLIN_SCALE: f64 = 0.5;
 pub const MAX_HEIGHT: f64 = 64.0;
+const GHOST_OPACITY: f32 = 0.5;
+/// Voxels merged along each side by every level of detail, the first being
+/// the full mesh.
+pub const LOD_FACTORS: [u8; 4] = [1, 2, 4, 8];
 
 #[derive(Debug)]
 pub struct Chunk {
@@ -27,6 +32,13 @@ impl Chunk {
         }
     }
 
+    pub fn set_voxel(&mut self, index: usize, voxel_id: u8) {
+        self.voxels[index] = voxel_id;
+        if voxel_id != 0 {
+            self.is_empty = false;
+        }
+    }
+
     fn build_voxels(&mut self, position: [f32; 3], seed: u32) {
         let perlin = Simplex::new(seed);
         let [cx, cy, cz] = [
@@ -186,6 +198,61 @@ impl Chunk {
         true
     }
 
+    /// Corners of one voxel face in drawing order. `face_id` follows the shader:
+    /// 0 top, 1 bottom, 2 +x, 3 -x, 4 +z, 5 -z.
+    pub(super) fn face_vertex(
+        position: [u8; 3],
+        voxel_id: u8,
+        face_id: u8,
+        ao: [u8; 4],
+        select: u8,
+    ) -> [[u8; 7]; 6] {
+        Chunk::face_vertex_sized(position, 1, voxel_id, face_id, ao, select)
+    }
+
+    /// Like `face_vertex`, for a cube `size` voxels wide.
+    fn face_vertex_sized(
+        position: [u8; 3],
+        size: u8,
+        voxel_id: u8,
+        face_id: u8,
+        ao: [u8; 4],
+        select: u8,
+    ) -> [[u8; 7]; 6] {
+        let [x, y, z] = position;
+        let s = size;
+        let (corners, order) = match face_id {
+            0 => (
+                [[x, y + s, z], [x + s, y + s, z], [x + s, y + s, z + s], [x, y + s, z + s]],
+                [1, 0, 3, 1, 3, 2],
+            ),
+            1 => (
+                [[x, y, z], [x + s, y, z], [x + s, y, z + s], [x, y, z + s]],
+                [3, 0, 2, 0, 1, 2],
+            ),
+            2 => (
+                [[x + s, y, z], [x + s, y + s, z], [x + s, y + s, z + s], [x + s, y, z + s]],
+                [2, 3, 0, 2, 0, 1],
+            ),
+            3 => (
+                [[x, y, z], [x, y + s, z], [x, y + s, z + s], [x, y, z + s]],
+                [2, 0, 3, 2, 1, 0],
+            ),
+            4 => (
+                [[x, y, z + s], [x, y + s, z + s], [x + s, y + s, z + s], [x + s, y, z + s]],
+                [1, 0, 3, 1, 3, 2],
+            ),
+            _ => (
+                [[x, y, z], [x, y + s, z], [x + s, y + s, z], [x + s, y, z]],
+                [1, 3, 0, 1, 2, 3],
+            ),
+        };
+        order.map(|i| {
+            let [x, y, z] = corners[i];
+            [x, y, z, voxel_id, face_id, ao[i], select]
+        })
+    }
+
     fn add_vertex(
         chunk_voxels: &mut Vec<ChunkVertexPacked>,
         vertex: &[[u8; 7]],
@@ -205,6 +272,7 @@ impl Chunk {
         world_voxels: &Vec<Vec<u8>>,
         sel: Option<usize>,
     ) -> Vec<ChunkVertexPacked> {
+        let _span = profiler::span("build_mesh");
         let mut vertex_data: Vec<ChunkVertexPacked> =
             vec![ChunkVertexPacked::pack_data(0, 0, 0, 0, 0, 0, 0); CHUNK_VOL * 15];
         let mut index = 0;
@@ -238,6 +306,7 @@ impl Chunk {
                         let wz = z as f32 + cz * CHUNK_SIZE as f32;
 
                         let (x, y, z) = (x as i32, y as i32, z as i32);
+                        let voxel = [x as u8, y as u8, z as u8];
                         if Chunk::voxel_is_void([x, y + 1, z], [wx, wy + 1.0, wz], world_voxels) {
                             let ao = Chunk::get_ao(
                                 [x, y + 1, z],
@@ -245,28 +314,16 @@ impl Chunk {
                                 world_voxels,
                                 Plane::Y,
                             );
-                            let (x, y, z) = (x as u8, y as u8, z as u8);
-                            let v0 = [x, y + 1, z, voxel_id, 0, ao[0], select];
-                            let v1 = [x + 1, y + 1, z, voxel_id, 0, ao[1], select];
-                            let v2 = [x + 1, y + 1, z + 1, voxel_id, 0, ao[2], select];
-                            let v3 = [x, y + 1, z + 1, voxel_id, 0, ao[3], select];
-
                             Chunk::add_vertex(
                                 &mut vertex_data,
-                                &[v1, v0, v3, v1, v3, v2],
+                                &Chunk::face_vertex(voxel, voxel_id, 0, ao, select),
                                 &mut index,
                             );
                         }
 
                         /*  if Chunk::voxel_is_void([x, y - 1, z], [wx, wy - 1.0, wz], world_voxels) {
                             let ao = Chunk::get_ao([x, y - 1, z], [wx, wy - 1.0, wz], world_voxels, Plane::Y);
-                            let (x, y, z) = (x as u8, y as u8, z as u8);
-                            let v0 = [x    , y, z    , voxel_id, 1, ao[0], select];
-                            let v1 = [x + 1, y, z    , voxel_id, 1, ao[1], select];
-                            let v2 = [x + 1, y, z + 1, voxel_id, 1, ao[2], select];
-                            let v3 = [x    , y, z + 1, voxel_id, 1, ao[3], select];
-
-                            Chunk::add_vertex(&mut vertex_data, &[v3, v0, v2, v0, v1, v2], &mut index);
+                            Chunk::add_vertex(&mut vertex_data, &Chunk::face_vertex(voxel, voxel_id, 1, ao, select), &mut index);
                         } */
 
                         //Front
@@ -277,15 +334,9 @@ impl Chunk {
                                 world_voxels,
                                 Plane::X,
                             );
-                            let (x, y, z) = (x as u8, y as u8, z as u8);
-                            let v0 = [x + 1, y, z, voxel_id, 2, ao[0], select];
-                            let v1 = [x + 1, y + 1, z, voxel_id, 2, ao[1], select];
-                            let v2 = [x + 1, y + 1, z + 1, voxel_id, 2, ao[2], select];
-                            let v3 = [x + 1, y, z + 1, voxel_id, 2, ao[3], select];
-
                             Chunk::add_vertex(
                                 &mut vertex_data,
-                                &[v2, v3, v0, v2, v0, v1],
+                                &Chunk::face_vertex(voxel, voxel_id, 2, ao, select),
                                 &mut index,
                             );
                         }
@@ -298,15 +349,9 @@ impl Chunk {
                                 world_voxels,
                                 Plane::X,
                             );
-                            let (x, y, z) = (x as u8, y as u8, z as u8);
-                            let v0 = [x, y, z, voxel_id, 3, ao[0], select];
-                            let v1 = [x, y + 1, z, voxel_id, 3, ao[1], select];
-                            let v2 = [x, y + 1, z + 1, voxel_id, 3, ao[2], select];
-                            let v3 = [x, y, z + 1, voxel_id, 3, ao[3], select];
-
                             Chunk::add_vertex(
                                 &mut vertex_data,
-                                &[v2, v0, v3, v2, v1, v0],
+                                &Chunk::face_vertex(voxel, voxel_id, 3, ao, select),
                                 &mut index,
                             );
                         }
@@ -318,15 +363,9 @@ impl Chunk {
                                 world_voxels,
                                 Plane::Z,
                             );
-                            let (x, y, z) = (x as u8, y as u8, z as u8);
-                            let v0 = [x, y, z + 1, voxel_id, 4, ao[0], select];
-                            let v1 = [x, y + 1, z + 1, voxel_id, 4, ao[1], select];
-                            let v2 = [x + 1, y + 1, z + 1, voxel_id, 4, ao[2], select];
-                            let v3 = [x + 1, y, z + 1, voxel_id, 4, ao[3], select];
-
                             Chunk::add_vertex(
                                 &mut vertex_data,
-                                &[v1, v0, v3, v1, v3, v2],
+                                &Chunk::face_vertex(voxel, voxel_id, 4, ao, select),
                                 &mut index,
                             );
                         }
@@ -338,15 +377,9 @@ impl Chunk {
                                 world_voxels,
                                 Plane::Z,
                             );
-                            let (x, y, z) = (x as u8, y as u8, z as u8);
-                            let v0 = [x, y, z, voxel_id, 5, ao[0], select];
-                            let v1 = [x, y + 1, z, voxel_id, 5, ao[1], select];
-                            let v2 = [x + 1, y + 1, z, voxel_id, 5, ao[2], select];
-                            let v3 = [x + 1, y, z, voxel_id, 5, ao[3], select];
-
                             Chunk::add_vertex(
                                 &mut vertex_data,
-                                &[v1, v3, v0, v1, v2, v3],
+                                &Chunk::face_vertex(voxel, voxel_id, 5, ao, select),
                                 &mut index,
                             );
                         }
@@ -354,19 +387,150 @@ impl Chunk {
                 }
             }
         }
+        vertex_data.truncate(index);
+        vertex_data
+    }
+
+    /// Mesh with `factor`³ voxels merged into each cell. A cell is solid when
+    /// any of its voxels is, so it covers the full mesh, and looks like its
+    /// highest voxel. Faces on the border of the chunk are only hidden when
+    /// every neighbour voxel they touch is solid, so no level of detail next
+    /// to them leaves a hole.
+    fn build_lod_mesh(
+        &self,
+        position: [f32; 3],
+        world_voxels: &Vec<Vec<u8>>,
+        factor: u8,
+    ) -> Vec<ChunkVertexPacked> {
+        let _span = profiler::span("build_lod_mesh");
+        let mut vertex_data = vec![];
+        if self.is_empty {
+            return vertex_data;
+        }
+
+        let cells = (CHUNK_SIZE / factor) as i32;
+        let index = |[x, y, z]: [i32; 3]| (x + cells * z + cells * cells * y) as usize;
+        let mut merged = vec![0; (cells * cells * cells) as usize];
+        for y in 0..cells {
+            for z in 0..cells {
+                for x in 0..cells {
+                    merged[index([x, y, z])] = self.merged_voxel([x, y, z], factor);
+                }
+            }
+        }
+
+        for y in 0..cells {
+            for z in 0..cells {
+                for x in 0..cells {
+                    let voxel_id = merged[index([x, y, z])];
+                    if voxel_id == 0 {
+                        continue;
+                    }
+                    let faces = [
+                        (0, [0, 1, 0]),
+                        (2, [1, 0, 0]),
+                        (3, [-1, 0, 0]),
+                        (4, [0, 0, 1]),
+                        (5, [0, 0, -1]),
+                    ];
+                    for (face_id, [dx, dy, dz]) in faces {
+                        let neighbour = [x + dx, y + dy, z + dz];
+                        let hidden = if neighbour.iter().all(|&c| (0..cells).contains(&c)) {
+                            merged[index(neighbour)] != 0
+                        } else {
+                            Chunk::border_is_solid(
+                                position,
+                                world_voxels,
+                                [x, y, z],
+                                [dx, dy, dz],
+                                factor,
+                            )
+                        };
+                        if hidden {
+                            continue;
+                        }
+                        let corner = [x as u8 * factor, y as u8 * factor, z as u8 * factor];
+                        for [x, y, z, voxel_id, face_id, shading_id, select] in
+                            Chunk::face_vertex_sized(corner, factor, voxel_id, face_id, [3; 4], 0)
+                        {
+                            vertex_data.push(ChunkVertexPacked::pack_data(
+                                x, y, z, voxel_id, face_id, shading_id, select,
+                            ));
+                        }
+                    }
+                }
+            }
+        }
         vertex_data
     }
+
+    /// Highest solid voxel of the cell, air when there is none.
+    fn merged_voxel(&self, cell: [i32; 3], factor: u8) -> u8 {
+        let f = factor as usize;
+        let [cx, cy, cz] = cell.map(|c| c as usize * f);
+        for y in (cy..cy + f).rev() {
+            for z in cz..cz + f {
+                for x in cx..cx + f {
+                    let voxel_id = self.voxels[x + CHUNK_SIZE as usize * z + CHUNK_AREA * y];
+                    if voxel_id != 0 {
+                        return voxel_id;
+                    }
+                }
+            }
+        }
+        0
+    }
+
+    /// Whether every voxel of the neighbouring chunk that the face of `cell`
+    /// toward `direction` touches is solid.
+    fn border_is_solid(
+        position: [f32; 3],
+        world_voxels: &Vec<Vec<u8>>,
+        cell: [i32; 3],
+        direction: [i32; 3],
+        factor: u8,
+    ) -> bool {
+        let f = factor as i32;
+        // First layer of voxels past the face, spread along the other two axes
+        let start: [i32; 3] = std::array::from_fn(|i| match direction[i] {
+            1 => (cell[i] + 1) * f,
+            -1 => cell[i] * f - 1,
+            _ => cell[i] * f,
+        });
+        let axes: Vec<usize> = (0..3).filter(|&i| direction[i] == 0).collect();
+        for a in 0..f {
+            for b in 0..f {
+                let mut local = start;
+                local[axes[0]] += a;
+                local[axes[1]] += b;
+                let global: [f32; 3] =
+                    std::array::from_fn(|i| local[i] as f32 + position[i] * CHUNK_SIZE as f32);
+                if Chunk::voxel_is_void(local, global, world_voxels) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
 }
 
 #[derive(Debug)]
 pub struct ChunkMesh {
     #[allow(dead_code)]
     pub chunk: Chunk,
-    vertex_buffer: wgpu::Buffer,
-    texture: BindTexture,
-    mesh_size: u32,
+    vertex: Vec<ChunkVertexPacked>,
+    /// Coarser meshes, one per level of detail past the full one, built the
+    /// first time they are drawn.
+    lod_vertex: [Option<Vec<ChunkVertexPacked>>; LOD_FACTORS.len() - 1],
+    /// Hash of the voxels the coarser meshes were built from.
+    lod_voxels: u64,
+    /// Index in `LOD_FACTORS` of the mesh drawn.
+    lod: usize,
     pub position: [f32; 3],
-    chunk_bind_group: wgpu::BindGroup,
+    opacity: f32,
+    /// Changes whenever the drawn vertices do, so the batch knows what to
+    /// upload.
+    version: u64,
 }
 
 impl ChunkMesh {
@@ -376,86 +540,144 @@ impl ChunkMesh {
         voxels.voxels
     }
 
-    pub fn new(
-        device: &wgpu::Device,
-        bytes: &[u8],
-        texture_bind_group_layout: &wgpu::BindGroupLayout,
-        queue: &wgpu::Queue,
-        position: [f32; 3],
-        world_voxels: &Vec<Vec<u8>>,
-        seed: u32
-    ) -> ChunkMesh {
+    pub fn new(position: [f32; 3], world_voxels: &Vec<Vec<u8>>, seed: u32) -> ChunkMesh {
         ChunkVertexPacked::pack_data(1, 1, 1, 1, 1, 1, 0);
         let mut chunk = Chunk::new();
         chunk.build_voxels(position, seed);
         let vertex = chunk.build_mesh(position, world_voxels, None);
-        let texture = BindTexture::new(texture_bind_group_layout, bytes, device, queue, "Terrain");
-        //let mut vertex: Vec<ChunkVertex> = vec![ChunkVertex::new(0, 0, 0, 0, 0, 0); mesh.len() + (CHUNK_VOL * 30 - mesh.len()) / 2];
-        let position_matrix = cgmath::Matrix4::from_translation(cgmath::Vector3::from([
-            position[0] - WORLD_W as f32 / 2.0,
-            position[1] - WORLD_H as f32,
-            position[2] - WORLD_D as f32 / 2.0,
-        ]));
-
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&vertex),
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let chunk_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Camera buffer"),
-            contents: bytemuck::cast_slice(&[Uniforms::new(position_matrix.into())]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
+        ChunkMesh::from_vertex(position, chunk, vertex, 1.0)
+    }
 
-        let chunk_bind_group_layout = device.create_bind_group_layout(&Uniforms::desc());
+    /// Translucent mesh that is not backed by world voxels, used to preview
+    /// edits before they are applied. `position` is in chunk units.
+    pub fn ghost(position: [f32; 3], vertex: Vec<ChunkVertexPacked>) -> ChunkMesh {
+        ChunkMesh::from_vertex(position, Chunk::new(), vertex, GHOST_OPACITY)
+    }
 
-        let chunk_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Chunk's uniforms bind group"),
-            layout: &chunk_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: chunk_buffer.as_entire_binding(),
-            }],
-        });
+    /// Caps of the voxels sliced by the cutaway view. `position` is in chunk
+    /// units.
+    pub fn section(position: [f32; 3], vertex: Vec<ChunkVertexPacked>) -> ChunkMesh {
+        ChunkMesh::from_vertex(position, Chunk::new(), vertex, 1.0)
+    }
 
+    /// Meshes only live on the CPU, `ChunkBatch` packs them into the buffers
+    /// they are drawn from.
+    fn from_vertex(
+        position: [f32; 3],
+        chunk: Chunk,
+        vertex: Vec<ChunkVertexPacked>,
+        opacity: f32,
+    ) -> ChunkMesh {
         Self {
-            chunk,
-            vertex_buffer,
-            texture,
-            mesh_size: vertex.len() as u32,
+            vertex,
+            lod_vertex: Default::default(),
+            lod_voxels: voxels_hash(&chunk.voxels),
+            lod: 0,
             position,
-            chunk_bind_group,
+            opacity,
+            version: next_version(),
+            chunk,
         }
     }
 
-    pub fn reflesh(
-        &mut self,
-        queue: &wgpu::Queue,
-        world_voxels: &Vec<Vec<u8>>,
-        select: Option<usize>,
-    ) {
-        let vertex = self.chunk.build_mesh(self.position, world_voxels, select);
+    /// Rebuilds the full mesh, highlighting the voxel at `select`. Coarser
+    /// meshes don't show the highlight, so a refresh for it keeps them unless
+    /// the voxels of the chunk changed. Without a selection they are always
+    /// rebuilt, as a neighbour may have changed.
+    pub fn reflesh(&mut self, world_voxels: &Vec<Vec<u8>>, select: Option<usize>) {
+        let _span = profiler::span("reflesh");
+        self.vertex = self.chunk.build_mesh(self.position, world_voxels, select);
+        let voxels = voxels_hash(&self.chunk.voxels);
+        if select.is_some() && voxels == self.lod_voxels {
+            if self.lod == 0 {
+                self.version = next_version();
+            }
+            return;
+        }
+        self.lod_voxels = voxels;
+        self.lod_vertex = Default::default();
+        if self.lod > 0 {
+            self.lod_vertex[self.lod - 1] = Some(self.build_lod(self.lod, world_voxels));
+        }
+        self.version = next_version();
+    }
 
-        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertex))
+    /// Draws the level of detail `lod`, an index in `LOD_FACTORS`, building
+    /// its mesh if it is not cached yet.
+    pub fn set_lod(&mut self, lod: usize, world_voxels: &Vec<Vec<u8>>) {
+        if lod == self.lod {
+            return;
+        }
+        if lod > 0 && self.lod_vertex[lod - 1].is_none() {
+            self.lod_vertex[lod - 1] = Some(self.build_lod(lod, world_voxels));
+        }
+        self.lod = lod;
+        self.version = next_version();
     }
 
-    pub fn render(
-        &self,
-        render_pass: &mut wgpu::RenderPass,
-        render_pipeline: &wgpu::RenderPipeline,
-        camera_bind_group: &wgpu::BindGroup,
-    ) {
-        if !self.chunk.is_empty {
-            render_pass.set_pipeline(render_pipeline);
-            render_pass.set_bind_group(0, &self.texture.diffuse_bind_group, &[]);
-            render_pass.set_bind_group(1, camera_bind_group, &[]);
-            render_pass.set_bind_group(2, &self.chunk_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.draw(0..self.mesh_size, 0..1);
+    pub fn lod(&self) -> usize {
+        self.lod
+    }
+
+    fn build_lod(&self, lod: usize, world_voxels: &Vec<Vec<u8>>) -> Vec<ChunkVertexPacked> {
+        self.chunk
+            .build_lod_mesh(self.position, world_voxels, LOD_FACTORS[lod])
+    }
+
+    /// Centre of the chunk in world units.
+    pub fn center(&self) -> [f32; 3] {
+        chunk_offset(self.position).map(|c| c + 0.5)
+    }
+
+    /// Moves the mesh without rebuilding it. `position` is in chunk units.
+    pub fn set_position(&mut self, position: [f32; 3]) {
+        self.position = position;
+    }
+
+    /// Vertices drawn by the batch, zero when the chunk is skipped.
+    pub fn vertex_count(&self) -> u32 {
+        if self.chunk.is_empty {
+            0
+        } else {
+            self.vertices().len() as u32
         }
     }
+
+    pub fn vertices(&self) -> &[ChunkVertexPacked] {
+        match self.lod {
+            0 => &self.vertex,
+            lod => self.lod_vertex[lod - 1].as_deref().unwrap_or_default(),
+        }
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Where and how opaque the mesh is drawn.
+    pub fn uniforms(&self) -> Uniforms {
+        Uniforms::new(chunk_offset(self.position), self.opacity)
+    }
+}
+
+fn voxels_hash(voxels: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    voxels.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Unique across every mesh, so a rebuilt mesh never looks unchanged.
+fn next_version() -> u64 {
+    static VERSION: AtomicU64 = AtomicU64::new(0);
+    VERSION.fetch_add(1, Ordering::Relaxed)
+}
+
+fn chunk_offset(position: [f32; 3]) -> [f32; 3] {
+    [
+        position[0] - WORLD_W as f32 / 2.0,
+        position[1] - WORLD_H as f32,
+        position[2] - WORLD_D as f32 / 2.0,
+    ]
 }
 
 #[repr(C)]
@@ -476,7 +698,7 @@ impl ChunkVertexPacked {
             }],
         }
     }
-    fn pack_data(
+    pub(super) fn pack_data(
         x: u8,
         y: u8,
         z: u8,
@@ -509,59 +731,28 @@ impl ChunkVertexPacked {
     }
 }
 
-#[repr(C)]
-#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
-struct UvUniform {
-    uv_coord: [f32; 2],
-    uv_index: u32,
-    _padding: u32,
-}
-impl UvUniform {
-    fn new(uv_coord: [f32; 2], uv_index: u32) -> Self {
-        Self {
-            uv_coord,
-            uv_index,
-            _padding: 0,
-        }
-    }
-}
-
+/// Record of a mesh in the storage buffer the chunk shader indexes by
+/// instance. The UVs of the faces are constants of the shader.
 #[repr(C)]
 #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Uniforms {
-    uv_uniform: [UvUniform; 12],
-    p_matrix: [[f32; 4]; 4],
+    /// Translation from chunk space to world space.
+    offset: [f32; 3],
+    opacity: f32,
 }
 
 impl Uniforms {
-    pub fn new(p_matrix: [[f32; 4]; 4]) -> Self {
-        let uv_uniform = [
-            UvUniform::new([0.0, 0.0], 1),
-            UvUniform::new([0.0, 1.0], 0),
-            UvUniform::new([1.0, 0.0], 2),
-            UvUniform::new([1.0, 1.0], 1),
-            UvUniform::new([0.0, 0.0], 2),
-            UvUniform::new([0.0, 0.0], 3),
-            UvUniform::new([0.0, 0.0], 3),
-            UvUniform::new([0.0, 0.0], 0),
-            UvUniform::new([0.0, 0.0], 2),
-            UvUniform::new([0.0, 0.0], 3),
-            UvUniform::new([0.0, 0.0], 1),
-            UvUniform::new([0.0, 1.0], 0),
-        ];
-        Self {
-            uv_uniform,
-            p_matrix,
-        }
+    pub fn new(offset: [f32; 3], opacity: f32) -> Self {
+        Self { offset, opacity }
     }
     pub fn desc() -> wgpu::BindGroupLayoutDescriptor<'static> {
         wgpu::BindGroupLayoutDescriptor {
-            label: Some("Chunk's UvUniforms bind group layout"),
+            label: Some("Chunk uniforms bind group layout"),
             entries: &[wgpu::BindGroupLayoutEntry {
                 binding: 0,
                 visibility: wgpu::ShaderStages::VERTEX,
                 ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
                     has_dynamic_offset: false,
                     min_binding_size: None,
                 },
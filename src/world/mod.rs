@@ -1,18 +1,45 @@
+pub mod batch;
+pub mod block;
 pub mod chunk;
+pub mod schematic;
 
-use cgmath::Matrix4;
+use std::collections::HashSet;
+
+use cgmath::Vector3;
 use chunk::*;
 use rand::prelude::*;
 
+use crate::utils::camera::CameraUniform;
+
 pub const WORLD_W: u32 = 5;
 pub const WORLD_H: u32 = 2;
 pub const WORLD_D: u32 = 5;
 pub const WORLD_AREA: u32 = WORLD_W * WORLD_D;
 pub const WORLD_VOL: u32 = WORLD_AREA * WORLD_H;
+/// Widest a merged voxel may look on screen, in pixels, before a chunk falls
+/// back to a finer level of detail.
+const LOD_PIXELS: f32 = 2.0;
+/// How far past `LOD_PIXELS` the merged voxels of a chunk may grow before it
+/// goes back to a finer level, so a view near the limit doesn't flip levels.
+const LOD_HYSTERESIS: f32 = 1.25;
 
 pub struct World {
     pub chunks: Vec<ChunkMesh>,
     pub voxels: Vec<Vec<u8>>,
+    pub ghost: Vec<ChunkMesh>,
+    dirty: HashSet<usize>,
+    /// Voxels from this height up are hidden and cannot be picked.
+    pub cut: Option<i32>,
+    /// Caps the voxels sliced by `cut` so their insides read as solid.
+    pub cross_section: bool,
+    section: Vec<ChunkMesh>,
+    section_vertex: Vec<Vec<ChunkVertexPacked>>,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        World::new()
+    }
 }
 
 impl World {
@@ -20,57 +47,207 @@ impl World {
         Self {
             chunks: vec![],
             voxels: vec![],
+            ghost: vec![],
+            dirty: HashSet::new(),
+            cut: None,
+            cross_section: false,
+            section: vec![],
+            section_vertex: vec![],
+        }
+    }
+
+    /// Chunk and voxel index of a voxel in world coordinates.
+    pub fn index(position: [i32; 3]) -> Option<(usize, usize)> {
+        let size = CHUNK_SIZE as i32;
+        let [x, y, z] = position;
+        if x < 0
+            || y < 0
+            || z < 0
+            || x >= WORLD_W as i32 * size
+            || y >= WORLD_H as i32 * size
+            || z >= WORLD_D as i32 * size
+        {
+            return None;
         }
+        let [cx, cy, cz] = [x / size, y / size, z / size];
+        let [lx, ly, lz] = [x % size, y % size, z % size];
+        let chunk_index = (cx + WORLD_W as i32 * cz + WORLD_AREA as i32 * cy) as usize;
+        let voxel_index = lx as usize + CHUNK_SIZE as usize * lz as usize + CHUNK_AREA * ly as usize;
+        Some((chunk_index, voxel_index))
     }
 
-    pub fn update(&mut self) {}
+    /// Centre of a voxel in the space chunks are drawn in, where a chunk
+    /// is one unit and the world is centred on the origin.
+    pub fn voxel_center(position: [i32; 3]) -> Vector3<f32> {
+        let size = CHUNK_SIZE as f32;
+        Vector3::new(
+            (position[0] as f32 + 0.5) / size - WORLD_W as f32 / 2.0,
+            (position[1] as f32 + 0.5) / size - WORLD_H as f32,
+            (position[2] as f32 + 0.5) / size - WORLD_D as f32 / 2.0,
+        )
+    }
 
-    pub fn render(
-        &self,
-        render_pass: &mut wgpu::RenderPass,
-        render_pipeline: &wgpu::RenderPipeline,
-        camera_bind_group: &wgpu::BindGroup,
-        camera_m: Matrix4<f32>,
-        relation: [f32; 2],
-    ) {
-        self.chunks
-            .iter()
-            /* .filter(|c| {
-                let [cx, cy, cz] = c.position;
-                let points = [
-                    [0.0, 0.0, 0.0],
-                    [0.0, 0.0, 1.0],
-                    [0.0, 1.0, 0.0],
-                    [0.0, 1.0, 1.0],
-                    [1.0, 0.0, 0.0],
-                    [1.0, 0.0, 1.0],
-                    [1.0, 1.0, 0.0],
-                    [1.0, 1.0, 1.0],
-                ];
-                let position_matrix = cgmath::Matrix4::from_translation(cgmath::Vector3::from([
-                    cx - WORLD_W as f32 / 2.0,
-                    cy - WORLD_H as f32,
-                    cz - WORLD_D as f32 / 2.0,
-                ]));
-                for i in points {
-                    let projec_point = camera_m * position_matrix * Vector3::from(i).extend(1.0);
-                    if  projec_point.x.abs() / relation[0] <= 1.0 || projec_point.y.abs() / relation[1] <= 1.0 {
-                        return true;
+    /// True when voxels at height `y` are hidden by the cutaway view.
+    pub fn is_cut(&self, y: i32) -> bool {
+        self.cut.is_some_and(|cut| y >= cut)
+    }
+
+    pub fn get_voxel(&self, position: [i32; 3]) -> u8 {
+        match World::index(position) {
+            Some((chunk_index, voxel_index)) => self.voxels[chunk_index][voxel_index],
+            None => 0,
+        }
+    }
+
+    /// Changes a voxel and marks every chunk whose mesh depends on it.
+    /// The meshes are rebuilt on the next `reflesh_dirty`.
+    pub fn set_voxel(&mut self, position: [i32; 3], voxel_id: u8) {
+        let Some((chunk_index, voxel_index)) = World::index(position) else {
+            return;
+        };
+        self.voxels[chunk_index][voxel_index] = voxel_id;
+        self.chunks[chunk_index].chunk.set_voxel(voxel_index, voxel_id);
+
+        let [x, y, z] = position;
+        for dy in -1..=1 {
+            for dz in -1..=1 {
+                for dx in -1..=1 {
+                    if let Some((chunk_index, _)) = World::index([x + dx, y + dy, z + dz]) {
+                        self.dirty.insert(chunk_index);
                     }
                 }
-                false
-            }) */
-            .for_each(|x| x.render(render_pass, render_pipeline, camera_bind_group))
-    }
-
-    pub fn build_chunk(
-        &mut self,
-        device: &wgpu::Device,
-        bytes: &[u8],
-        texture_bind_group_layout: &wgpu::BindGroupLayout,
-        queue: &wgpu::Queue,
-    ) {
+            }
+        }
+    }
+
+    /// Applies a batch of changes and rebuilds each touched chunk once.
+    pub fn edit(&mut self, edits: &[([i32; 3], u8)]) {
+        for &(position, voxel_id) in edits {
+            self.set_voxel(position, voxel_id);
+        }
+        self.reflesh_dirty();
+    }
+
+    pub fn reflesh_dirty(&mut self) {
+        for chunk_index in self.dirty.drain() {
+            self.chunks[chunk_index].reflesh(&self.voxels, None);
+        }
+    }
+
+    pub fn update(&mut self, _dt: f32) {}
+
+    /// Draws each chunk at the coarsest level of detail whose merged voxels
+    /// stay within `LOD_PIXELS` on a `width` by `height` screen seen through
+    /// `camera`. A chunk keeps a coarser level until its voxels are past the
+    /// limit by `LOD_HYSTERESIS`.
+    pub fn update_lods(&mut self, camera: &CameraUniform, width: u32, height: u32) {
+        let voxel = 1.0 / CHUNK_SIZE as f32;
+        for chunk in &mut self.chunks {
+            let pixels = camera.projected_size(chunk.center(), voxel, width as f32, height as f32);
+            let lod = LOD_FACTORS
+                .iter()
+                .rposition(|&factor| pixels * factor as f32 <= LOD_PIXELS)
+                .unwrap_or(0);
+            let current = LOD_FACTORS[chunk.lod()] as f32;
+            let lod = if lod < chunk.lod() && pixels * current <= LOD_PIXELS * LOD_HYSTERESIS {
+                chunk.lod()
+            } else {
+                lod
+            };
+            chunk.set_lod(lod, &self.voxels);
+        }
+    }
+
+    /// Chunks drawn at each level of detail of `LOD_FACTORS`.
+    pub fn lod_counts(&self) -> [usize; LOD_FACTORS.len()] {
+        let mut counts = [0; LOD_FACTORS.len()];
+        for chunk in &self.chunks {
+            counts[chunk.lod()] += 1;
+        }
+        counts
+    }
+
+    /// Top faces for the solid voxels right under the cut whose top is
+    /// covered, in the chunk column `cx, cz`. Darkened to tell them apart.
+    fn section_vertex(&self, cut: i32, cx: i32, cz: i32) -> Vec<ChunkVertexPacked> {
+        let size = CHUNK_SIZE as i32;
+        let y = cut - 1;
+        let mut vertex_data = vec![];
+        for lz in 0..size {
+            for lx in 0..size {
+                let [x, z] = [cx * size + lx, cz * size + lz];
+                let voxel_id = self.get_voxel([x, y, z]);
+                // Uncovered tops are already in the chunk mesh
+                if voxel_id == 0 || self.get_voxel([x, y + 1, z]) == 0 {
+                    continue;
+                }
+                let local = [lx as u8, y.rem_euclid(size) as u8, lz as u8];
+                for [x, y, z, voxel_id, face_id, shading_id, select] in
+                    Chunk::face_vertex(local, voxel_id, 0, [1; 4], 0)
+                {
+                    vertex_data.push(ChunkVertexPacked::pack_data(
+                        x, y, z, voxel_id, face_id, shading_id, select,
+                    ));
+                }
+            }
+        }
+        vertex_data
+    }
+
+    /// Rebuilds the cross-section when the cut or the voxels it slices
+    /// changed since the last call.
+    pub fn update_section(&mut self) {
+        let height = (WORLD_H * CHUNK_SIZE as u32) as i32;
+        let cut = self.cut.filter(|&cut| self.cross_section && cut > 0 && cut < height);
+        let Some(cut) = cut else {
+            self.section.clear();
+            self.section_vertex.clear();
+            return;
+        };
+
+        let mut columns = vec![];
+        for cz in 0..WORLD_D as i32 {
+            for cx in 0..WORLD_W as i32 {
+                columns.push(([cx, cz], self.section_vertex(cut, cx, cz)));
+            }
+        }
+        let unchanged = columns.len() == self.section_vertex.len()
+            && columns.iter().zip(&self.section_vertex).all(|((_, new), old)| {
+                bytemuck::cast_slice::<_, u8>(new) == bytemuck::cast_slice::<_, u8>(old)
+            });
+        if unchanged {
+            return;
+        }
+
+        let cy = ((cut - 1) / CHUNK_SIZE as i32) as f32;
+        self.section = columns
+            .iter()
+            .filter(|(_, vertex)| !vertex.is_empty())
+            .map(|([cx, cz], vertex)| ChunkMesh::section([*cx as f32, cy, *cz as f32], vertex.clone()))
+            .collect();
+        self.section_vertex = columns.into_iter().map(|(_, vertex)| vertex).collect();
+    }
+
+    /// Every mesh to draw, chunks first and the translucent ghost last.
+    pub fn meshes(&self) -> impl Iterator<Item = &ChunkMesh> + Clone {
+        self.chunks.iter().chain(&self.section).chain(&self.ghost)
+    }
+
+    /// Chunks drawn and the vertices they submit, ghost and cross-section
+    /// included.
+    pub fn draw_stats(&self) -> (usize, u64) {
+        let counts = self.meshes().map(|c| c.vertex_count());
+        let drawn = counts.clone().filter(|&c| c > 0).count();
+        (drawn, counts.map(|c| c as u64).sum())
+    }
+
+    pub fn build_chunk(&mut self) {
         let seed: u32 = random();
+        self.build_seeded(seed)
+    }
+
+    /// Generates the same terrain every time for a given `seed`.
+    pub fn build_seeded(&mut self, seed: u32) {
         for y in 0..WORLD_H {
             for z in 0..WORLD_D {
                 for x in 0..WORLD_W {
@@ -82,15 +259,7 @@ impl World {
         for y in 0..WORLD_H {
             for z in 0..WORLD_D {
                 for x in 0..WORLD_W {
-                    let chunk = ChunkMesh::new(
-                        device,
-                        bytes,
-                        texture_bind_group_layout,
-                        queue,
-                        [x as f32, y as f32, z as f32],
-                        &self.voxels,
-                        seed
-                    );
+                    let chunk = ChunkMesh::new([x as f32, y as f32, z as f32], &self.voxels, seed);
                     self.chunks.push(chunk);
                 }
             }
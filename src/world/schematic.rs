@@ -0,0 +1,279 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use super::chunk::{Chunk, ChunkVertexPacked, CHUNK_SIZE};
+use super::World;
+
+const MAGIC: &[u8; 4] = b"ISCH";
+const VERSION: u8 = 1;
+
+/// A box of voxels cut out of a world, stored with the same x, z, y
+/// ordering as a chunk.
+#[derive(Clone, Debug)]
+pub struct Schematic {
+    pub size: [u32; 3],
+    pub voxels: Vec<u8>,
+}
+
+impl Schematic {
+    /// Copies the voxels between two corners, both included.
+    pub fn copy(world: &World, a: [i32; 3], b: [i32; 3]) -> Self {
+        let min = [a[0].min(b[0]), a[1].min(b[1]), a[2].min(b[2])];
+        let max = [a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2])];
+        let size = [
+            (max[0] - min[0] + 1) as u32,
+            (max[1] - min[1] + 1) as u32,
+            (max[2] - min[2] + 1) as u32,
+        ];
+
+        let mut voxels = Vec::with_capacity((size[0] * size[1] * size[2]) as usize);
+        for y in min[1]..=max[1] {
+            for z in min[2]..=max[2] {
+                for x in min[0]..=max[0] {
+                    voxels.push(world.get_voxel([x, y, z]));
+                }
+            }
+        }
+
+        Self { size, voxels }
+    }
+
+    fn index(&self, x: u32, y: u32, z: u32) -> usize {
+        (x + self.size[0] * z + self.size[0] * self.size[2] * y) as usize
+    }
+
+    pub fn get(&self, position: [i32; 3]) -> u8 {
+        let [x, y, z] = position;
+        if x < 0
+            || y < 0
+            || z < 0
+            || x >= self.size[0] as i32
+            || y >= self.size[1] as i32
+            || z >= self.size[2] as i32
+        {
+            return 0;
+        }
+        self.voxels[self.index(x as u32, y as u32, z as u32)]
+    }
+
+    /// Rotates a quarter turn around the Y axis.
+    pub fn rotate_y(&mut self) {
+        let [sx, sy, sz] = self.size;
+        let mut rotated = Self {
+            size: [sz, sy, sx],
+            voxels: vec![0; self.voxels.len()],
+        };
+        for y in 0..sy {
+            for z in 0..sz {
+                for x in 0..sx {
+                    let index = rotated.index(sz - 1 - z, y, x);
+                    rotated.voxels[index] = self.voxels[self.index(x, y, z)];
+                }
+            }
+        }
+        *self = rotated;
+    }
+
+    /// Mirrors along the X axis.
+    pub fn mirror_x(&mut self) {
+        let [sx, sy, sz] = self.size;
+        for y in 0..sy {
+            for z in 0..sz {
+                for x in 0..sx / 2 {
+                    let (a, b) = (self.index(x, y, z), self.index(sx - 1 - x, y, z));
+                    self.voxels.swap(a, b);
+                }
+            }
+        }
+    }
+
+    /// Writes every non empty voxel with `origin` as the lowest corner.
+    /// Air is skipped so pasting never carves into the terrain.
+    pub fn paste(&self, world: &mut World, origin: [i32; 3]) {
+        let [sx, sy, sz] = self.size;
+        for y in 0..sy {
+            for z in 0..sz {
+                for x in 0..sx {
+                    let voxel_id = self.voxels[self.index(x, y, z)];
+                    if voxel_id == 0 {
+                        continue;
+                    }
+                    world.set_voxel(
+                        [origin[0] + x as i32, origin[1] + y as i32, origin[2] + z as i32],
+                        voxel_id,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Mesh of the `CHUNK_SIZE` box starting at `offset`, with full light on
+    /// every face. Used for the paste preview.
+    pub fn build_mesh(&self, offset: [u32; 3]) -> Vec<ChunkVertexPacked> {
+        let mut vertex_data = vec![];
+        let size = CHUNK_SIZE as u32;
+        let end = [
+            self.size[0].min(offset[0] + size),
+            self.size[1].min(offset[1] + size),
+            self.size[2].min(offset[2] + size),
+        ];
+
+        for y in offset[1]..end[1] {
+            for z in offset[2]..end[2] {
+                for x in offset[0]..end[0] {
+                    let voxel_id = self.voxels[self.index(x, y, z)];
+                    if voxel_id == 0 {
+                        continue;
+                    }
+                    let (wx, wy, wz) = (x as i32, y as i32, z as i32);
+                    let faces = [
+                        (0, [wx, wy + 1, wz]),
+                        (2, [wx + 1, wy, wz]),
+                        (3, [wx - 1, wy, wz]),
+                        (4, [wx, wy, wz + 1]),
+                        (5, [wx, wy, wz - 1]),
+                    ];
+                    let local = [
+                        (x - offset[0]) as u8,
+                        (y - offset[1]) as u8,
+                        (z - offset[2]) as u8,
+                    ];
+                    for (face_id, neighbour) in faces {
+                        if self.get(neighbour) != 0 {
+                            continue;
+                        }
+                        for [x, y, z, voxel_id, face_id, shading_id, select] in
+                            Chunk::face_vertex(local, voxel_id, face_id, [3; 4], 0)
+                        {
+                            vertex_data.push(ChunkVertexPacked::pack_data(
+                                x, y, z, voxel_id, face_id, shading_id, select,
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        vertex_data
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        for size in self.size {
+            writer.write_all(&size.to_le_bytes())?;
+        }
+        writer.write_all(&self.voxels)?;
+        writer.flush()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC || header[4] != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a schematic file",
+            ));
+        }
+
+        let mut size = [0; 3];
+        for value in size.iter_mut() {
+            let mut bytes = [0; 4];
+            reader.read_exact(&mut bytes)?;
+            *value = u32::from_le_bytes(bytes);
+        }
+
+        let mut voxels = vec![];
+        reader.read_to_end(&mut voxels)?;
+        if voxels.len() as u64 != size[0] as u64 * size[1] as u64 * size[2] as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "schematic size does not match its data",
+            ));
+        }
+
+        Ok(Self { size, voxels })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 3 by 2 by 4 box where every voxel has a different id.
+    fn numbered() -> Schematic {
+        Schematic {
+            size: [3, 2, 4],
+            voxels: (1..=24).collect(),
+        }
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("{}_{}.isch", name, std::process::id()))
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let schematic = numbered();
+        let path = temp_path("round_trip");
+        schematic.save(&path).unwrap();
+        let loaded = Schematic::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.size, schematic.size);
+        assert_eq!(loaded.voxels, schematic.voxels);
+    }
+
+    #[test]
+    fn load_rejects_truncated_data() {
+        let path = temp_path("truncated");
+        numbered().save(&path).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.pop();
+        std::fs::write(&path, bytes).unwrap();
+        let loaded = Schematic::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn load_rejects_other_files() {
+        let path = temp_path("not_schematic");
+        std::fs::write(&path, b"PNG\0 and more").unwrap();
+        let loaded = Schematic::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rotate_y_moves_voxels_a_quarter_turn() {
+        let mut schematic = numbered();
+        schematic.rotate_y();
+        assert_eq!(schematic.size, [4, 2, 3]);
+        let original = numbered();
+        for y in 0..2 {
+            for z in 0..4 {
+                for x in 0..3 {
+                    assert_eq!(schematic.get([3 - z, y, x]), original.get([x, y, z]));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn four_rotations_give_back_the_original() {
+        let mut schematic = numbered();
+        for _ in 0..4 {
+            schematic.rotate_y();
+        }
+        let original = numbered();
+        assert_eq!(schematic.size, original.size);
+        assert_eq!(schematic.voxels, original.voxels);
+    }
+}
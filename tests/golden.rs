@@ -0,0 +1,130 @@
+//! Renders fixed worlds offscreen and compares them against the reference
+//! images in `tests/golden`. Run with `UPDATE_GOLDEN=1` to rewrite them
+//! after an intended visual change.
+
+use std::path::PathBuf;
+
+use camera::{Camera, Error, State, World};
+use image::RgbaImage;
+
+const SIZE: u32 = 256;
+const SEED: u32 = 1234;
+/// Largest difference of a channel still counted as the same pixel.
+const CHANNEL_TOLERANCE: u8 = 8;
+/// Share of the pixels allowed to differ, for rasterizer differences at
+/// the edges of faces.
+const PIXEL_TOLERANCE: f32 = 0.01;
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{}.png", name))
+}
+
+/// Renders the seeded world seen from `setup`, at the levels of detail the
+/// view picks when `lod` is set, or `None` when the machine has no adapter
+/// to render with.
+fn render(sample_count: u32, lod: bool, setup: impl Fn(&mut Camera)) -> Option<RgbaImage> {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let mut state = match runtime.block_on(State::headless(SIZE, SIZE)) {
+        Ok(state) => state,
+        Err(Error::NoAdapter) => {
+            eprintln!("No adapter, skipping golden image test");
+            return None;
+        }
+        Err(e) => panic!("{}", e),
+    };
+    state.set_sample_count(sample_count);
+    let mut world = World::new();
+    world.build_seeded(SEED);
+    // Frame the whole world
+    let mut camera = state.camera;
+    camera.scale = 1.0;
+    camera.position = [0.0, 0.0];
+    setup(&mut camera);
+    if lod {
+        state.write_camera(&mut camera);
+        world.update_lods(&state.camera_uniform, SIZE, SIZE);
+    }
+    match state.render_to_image(&world, &mut camera) {
+        Ok(image) => Some(image),
+        Err(e) => panic!("{}", e),
+    }
+}
+
+fn check(name: &str, setup: impl Fn(&mut Camera)) {
+    check_with(name, 1, false, setup);
+}
+
+fn check_with(name: &str, sample_count: u32, lod: bool, setup: impl Fn(&mut Camera)) {
+    let Some(image) = render(sample_count, lod, setup) else {
+        return;
+    };
+    let path = golden_path(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        image.save(&path).unwrap();
+        return;
+    }
+
+    let expected = image::open(&path)
+        .unwrap_or_else(|e| panic!("missing reference {}: {}", path.display(), e))
+        .to_rgba8();
+    assert_eq!(image.dimensions(), expected.dimensions(), "{}: size", name);
+    let differing = image
+        .pixels()
+        .zip(expected.pixels())
+        .filter(|(a, b)| {
+            a.0.iter()
+                .zip(b.0.iter())
+                .any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE)
+        })
+        .count();
+    let share = differing as f32 / (SIZE * SIZE) as f32;
+    if share > PIXEL_TOLERANCE {
+        let actual = path.with_file_name(format!("{}.actual.png", name));
+        image.save(&actual).unwrap();
+        panic!(
+            "{}: {:.2}% of the pixels differ, see {}",
+            name,
+            share * 100.0,
+            actual.display()
+        );
+    }
+}
+
+#[test]
+fn isometric_0() {
+    check("isometric_0", |camera| camera.ang[1] = 0.0);
+}
+
+#[test]
+fn isometric_90() {
+    check("isometric_90", |camera| camera.ang[1] = 90.0);
+}
+
+#[test]
+fn isometric_180() {
+    check("isometric_180", |camera| camera.ang[1] = 180.0);
+}
+
+#[test]
+fn isometric_270() {
+    check("isometric_270", |camera| camera.ang[1] = 270.0);
+}
+
+#[test]
+fn perspective() {
+    check("perspective", |camera| camera.perspective = true);
+}
+
+#[test]
+fn multisampled() {
+    check_with("multisampled", 4, false, |camera| camera.ang[1] = 0.0);
+}
+
+#[test]
+fn lod() {
+    check_with("lod", 1, true, |camera| camera.scale = 0.5);
+}